@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use minotetris::*;
+use enumset::EnumSet;
+
+use crate::*;
+
+#[derive(Copy, Clone)]
+enum Step {
+    Hold,
+    Rotate(TetrisInput),
+    /// Move `moves` columns, either tapping or holding the key and letting DAS/ARR repeat it.
+    Shift {
+        input: TetrisInput,
+        moves: i32,
+        auto_repeat: bool,
+        /// Give up after this many frames, in case something is in the way.
+        timeout: u32
+    },
+    /// Soft drop until the piece lands.
+    Drop,
+    HardDrop
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum DriveOutcome {
+    Landed,
+    /// The piece was hard dropped somewhere other than the target.
+    Missed(Piece)
+}
+
+/// Why a move can't be played in a game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DriveError {
+    /// The path turns the piece 180 degrees, but the game doesn't have 180 rotation.
+    NoRotate180,
+    /// Holding and hard dropping aren't part of the path.
+    UnexpectedInput(TetrisInput)
+}
+
+impl fmt::Display for DriveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRotate180 => write!(f, "the move turns 180 degrees, which the game doesn't allow"),
+            Self::UnexpectedInput(input) => write!(f, "{:?} can't be part of a path", input)
+        }
+    }
+}
+
+impl std::error::Error for DriveError {}
+
+/// Plays a move back as per-frame `TetrisInput`s, watching the falling piece
+/// so keys are released as soon as they have done their job.
+pub struct InputDriver {
+    steps: VecDeque<Step>,
+    target: Piece,
+    prev_inputs: EnumSet<TetrisInput>,
+    shift_start: Option<i32>,
+    shift_frames: u32,
+    outcome: Option<DriveOutcome>
+}
+
+impl InputDriver {
+    /// Plays `path` from the spawn position and then hard drops, landing on `target`.
+    /// A `SoftDrop` in the path drops the piece all the way down.
+    /// `board` is the board the move will be played on.
+    pub fn new(
+        target: Piece,
+        uses_hold: bool,
+        path: &[TetrisInput],
+        board: &Board,
+        config: &TetrisGameConfig
+    ) -> Result<Self, DriveError> {
+        let mut steps = VecDeque::new();
+        if uses_hold {
+            steps.push_back(Step::Hold);
+        }
+        let mut piece = Piece::spawn(board, target.kind, config.rotation_system, config.spin_rule);
+        let mut path = path.iter().copied().peekable();
+        while let Some(input) = path.next() {
+            match input {
+                TetrisInput::Left | TetrisInput::Right => {
+                    let shift: fn(&mut Piece, &Board) -> bool = if input == TetrisInput::Left {
+                        Piece::move_left
+                    } else {
+                        Piece::move_right
+                    };
+                    let mut moves = 1;
+                    shift(&mut piece, board);
+                    while path.peek() == Some(&input) {
+                        path.next();
+                        moves += 1;
+                        shift(&mut piece, board);
+                    }
+                    let against_wall = !shift(&mut piece.clone(), board);
+                    steps.push_back(Step::Shift {
+                        input,
+                        moves,
+                        auto_repeat: Self::auto_repeat(moves, against_wall, config),
+                        timeout: config.das + (config.arr + 2) * moves as u32 + 2
+                    });
+                }
+                TetrisInput::RotLeft => {
+                    piece.turn_left(board);
+                    steps.push_back(Step::Rotate(input));
+                }
+                TetrisInput::RotRight => {
+                    piece.turn_right(board);
+                    steps.push_back(Step::Rotate(input));
+                }
+                TetrisInput::Rot180 => {
+                    piece.turn_180(board, config.rotate_180.ok_or(DriveError::NoRotate180)?);
+                    steps.push_back(Step::Rotate(input));
+                }
+                TetrisInput::SoftDrop => {
+                    piece.sonic_drop(board);
+                    steps.push_back(Step::Drop);
+                }
+                TetrisInput::Hold | TetrisInput::HardDrop => return Err(DriveError::UnexpectedInput(input))
+            }
+        }
+        steps.push_back(Step::HardDrop);
+        Ok(Self {
+            steps,
+            target,
+            prev_inputs: EnumSet::new(),
+            shift_start: None,
+            shift_frames: 0,
+            outcome: None
+        })
+    }
+
+    /// Whether holding the key gets there faster than tapping it.
+    /// With no ARR, holding the key slides the piece all the way to the wall.
+    fn auto_repeat(moves: i32, against_wall: bool, config: &TetrisGameConfig) -> bool {
+        if moves < 2 {
+            false
+        } else if config.arr == 0 {
+            against_wall && config.das + 1 < 2 * moves as u32 - 1
+        } else {
+            config.das + 1 + (moves as u32 - 2) * config.arr < 2 * moves as u32 - 1
+        }
+    }
+
+    /// Inputs for the next frame of `game`.
+    pub fn update(&mut self, game: &TetrisGame) -> EnumSet<TetrisInput> {
+        let inputs = self.next_inputs(game);
+        self.prev_inputs = inputs;
+        inputs
+    }
+
+    fn next_inputs(&mut self, game: &TetrisGame) -> EnumSet<TetrisInput> {
+        let piece = match game.get_state() {
+            TetrisGameState::PieceFalling(piece) => *piece,
+            _ => return EnumSet::new()
+        };
+        while let Some(&step) = self.steps.front() {
+            let input = match step {
+                Step::Hold => TetrisInput::Hold,
+                Step::Rotate(input) => input,
+                Step::Shift { input, moves, auto_repeat, timeout } => {
+                    let start = *self.shift_start.get_or_insert(piece.x);
+                    if (piece.x - start).abs() >= moves || self.shift_frames >= timeout {
+                        self.shift_start = None;
+                        self.shift_frames = 0;
+                        self.steps.pop_front();
+                        continue;
+                    }
+                    self.shift_frames += 1;
+                    if auto_repeat {
+                        return EnumSet::only(input);
+                    }
+                    input
+                }
+                Step::Drop => {
+                    if piece.clone().soft_drop(game.get_board()) {
+                        return EnumSet::only(TetrisInput::SoftDrop);
+                    }
+                    self.steps.pop_front();
+                    continue;
+                }
+                Step::HardDrop => TetrisInput::HardDrop
+            };
+            if self.prev_inputs.contains(input) {
+                // The key has to be let go for a frame before it registers again.
+                return EnumSet::new();
+            }
+            match step {
+                Step::Shift { .. } => {}
+                Step::HardDrop => {
+                    let mut landed = piece;
+                    landed.sonic_drop(game.get_board());
+                    self.outcome = Some(if self.target.same_placement(&landed) {
+                        DriveOutcome::Landed
+                    } else {
+                        DriveOutcome::Missed(landed)
+                    });
+                    self.steps.pop_front();
+                }
+                _ => {
+                    self.steps.pop_front();
+                }
+            }
+            return EnumSet::only(input);
+        }
+        EnumSet::new()
+    }
+
+    /// Whether the move has been hard dropped.
+    pub fn finished(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    pub fn outcome(&self) -> Option<DriveOutcome> {
+        self.outcome
+    }
+}
@@ -3,10 +3,7 @@ use std::collections::VecDeque;
 use minotetris::*;
 use rand::prelude::*;
 use enumset::{EnumSet, EnumSetType};
-
-const ATTACK_TABLE: [u32; 4] = [0, 1, 2, 4];
-const TSPIN_MULTIPLIER: u32 = 2;
-const B2B_BONUS: u32 = 1;
+use serde::{Serialize, Deserialize};
 
 #[derive(Debug, EnumSetType)]
 pub enum TetrisInput {
@@ -16,13 +13,14 @@ pub enum TetrisInput {
     Right,
     RotLeft,
     RotRight,
+    Rot180,
     Hold
 }
 
 pub enum TetrisGameState {
     SpawnDelay(u32),
     PieceFalling(Piece),
-    LineClearDelay(LockResult, TspinType, u32),
+    LineClearDelay(LockResult, u32),
     GameOver
 }
 
@@ -35,22 +33,76 @@ pub struct TetrisGame {
     prev_inputs: EnumSet<TetrisInput>,
     das_timer: u32,
     arr_timer: u32,
+    gravity_progress: f32,
+    lock_timer: u32,
+    lock_resets: u32,
+    lowest_y: i32,
+    lines: u32,
     config: TetrisGameConfig
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct TetrisGameConfig {
-    queue: u32,
-    spawn_delay: u32,
-    line_clear_delay: u32,
-    das: u32,
-    arr: u32
+    pub queue: u32,
+    pub randomizer: RandomizerKind,
+    pub spawn_delay: u32,
+    pub line_clear_delay: u32,
+    pub das: u32,
+    pub arr: u32,
+    /// Cells per frame the piece falls at each level. The last entry is used for any
+    /// higher level, and no entries means no gravity. 20 or more drops the piece instantly.
+    pub gravity: Vec<f32>,
+    /// Lines to clear before moving up a level.
+    pub lines_per_level: u32,
+    /// How many times faster than gravity the piece falls while soft dropping,
+    /// but never slower than a cell per frame.
+    pub soft_drop_factor: f32,
+    /// Frames a piece can rest on the stack before it locks on its own.
+    /// `None` means pieces only lock when hard dropped.
+    pub lock_delay: Option<u32>,
+    /// How many moves or rotations on the stack restart the lock delay.
+    /// Reaching a new lowest row allows that many again.
+    pub lock_resets: u32,
+    pub rotation_system: RotationSystem,
+    pub spin_rule: SpinRule,
+    /// Kick table for 180 rotations, or `None` to ignore `TetrisInput::Rot180`.
+    pub rotate_180: Option<Kicks180>,
+    pub attack: AttackRules
+}
+
+impl Default for TetrisGameConfig {
+    fn default() -> Self {
+        TetrisGameConfig {
+            queue: 5,
+            randomizer: RandomizerKind::default(),
+            spawn_delay: 7,
+            line_clear_delay: 20,
+            das: 10,
+            arr: 2,
+            gravity: Vec::new(),
+            lines_per_level: 10,
+            soft_drop_factor: 20.0,
+            lock_delay: None,
+            lock_resets: 15,
+            rotation_system: RotationSystem::Srs,
+            spin_rule: SpinRule::TSpin,
+            rotate_180: None,
+            attack: AttackRules::default()
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum TetrisGameEvent {
     PieceSpawned {
         queued_piece: PieceType
     },
     PieceLocked(LockResult),
+    /// The lock delay ran out. Comes right before the `PieceLocked`.
+    PieceAutoLocked,
+    /// Lines of attack from a clear, before cancelling pending garbage.
+    Attack(u32),
     GameOver,
     GarbageSent(u32),
     GarbageAdded(u32)
@@ -62,11 +114,16 @@ impl TetrisGame {
             state: TetrisGameState::SpawnDelay(0),
             board: Board::new(),
             held: false,
-            queue: PieceQueue::new(config.queue as usize, rng),
+            queue: PieceQueue::new(config.queue as usize, config.randomizer.build(), rng),
             garbage_pending: 0,
             prev_inputs: EnumSet::new(),
             das_timer: 0,
             arr_timer: 0,
+            gravity_progress: 0.0,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_y: 0,
+            lines: 0,
             config
         }
     }
@@ -79,104 +136,15 @@ impl TetrisGame {
         
         match &mut self.state {
             TetrisGameState::PieceFalling(piece) => {
-                if inputs.contains(TetrisInput::Hold) && !self.held {
-                    if let Some(kind) = self.board.hold.replace(piece.kind) {
-                        *piece = Piece::spawn(&self.board, kind);
-                        if !self.board.piece_fits(*piece) {
-                            self.state = TetrisGameState::GameOver;
-                            events.push(TetrisGameEvent::GameOver);
-                        }
-                    } else {
-                        self.state = TetrisGameState::SpawnDelay(0);
-                    }
-                    self.held = true;
-                } else {
-                    if inputs.contains(TetrisInput::Left) != inputs.contains(TetrisInput::Right) {
-                        let dir = if inputs.contains(TetrisInput::Left) {
-                            TetrisInput::Left
-                        } else {
-                            TetrisInput::Right
-                        };
-                        let prev_dir = if self.prev_inputs.contains(TetrisInput::Left) != self.prev_inputs.contains(TetrisInput::Right) {
-                            Some(if self.prev_inputs.contains(TetrisInput::Left) {
-                                TetrisInput::Left
-                            } else {
-                                TetrisInput::Right
-                            })
-                        } else {
-                            None
-                        };
-                        if Some(dir) != prev_dir {
-                            self.das_timer = 0;
-                        }
-            
-                        if self.das_timer == 0 {
-                            if dir == TetrisInput::Left {
-                                piece.move_left(&self.board);
-                            } else {
-                                piece.move_right(&self.board);
-                            }
-                        }
-                        if self.das_timer == self.config.das {
-                            loop {
-                                if self.arr_timer == 0 {
-                                    let success = if dir == TetrisInput::Left {
-                                        piece.move_left(&self.board)
-                                    } else {
-                                        piece.move_right(&self.board)
-                                    };
-                                    if !success {
-                                        break;
-                                    }
-                                }
-                                if self.arr_timer < self.config.arr {
-                                    break;
-                                }
-                                self.arr_timer -= self.config.arr;
-                            }
-                            self.arr_timer += 1;
-                        } else {
-                            self.das_timer += 1;
-                        }
-                    }
-            
-                    if inputs.contains(TetrisInput::RotLeft) != inputs.contains(TetrisInput::RotRight) {
-                        if inputs.contains(TetrisInput::RotLeft) {
-                            piece.turn_left(&self.board);
-                        } else {
-                            piece.turn_right(&self.board);
-                        }
-                    }
-            
-                    if inputs.contains(TetrisInput::SoftDrop) {
-                        piece.soft_drop(&self.board);
-                    }
-            
-                    if inputs.contains(TetrisInput::HardDrop) {
-                        piece.sonic_drop(&self.board);
-                        let result = self.board.lock_piece(*piece);
-                        events.push(TetrisGameEvent::PieceLocked(result));
-
-                        if result.lines_cleared > 0 {
-                            self.state = TetrisGameState::LineClearDelay(result, piece.tspin, 0);
-                        } else {
-                            if self.apply_garbage(garbage_rng, &mut events) && result.block_out {
-                                self.state = TetrisGameState::GameOver;
-                                events.push(TetrisGameEvent::GameOver);
-                            }
-                        }
-                    }
-                }                
+                let piece = *piece;
+                self.update_piece(piece, inputs, garbage_rng, &mut events);
             }
-            TetrisGameState::LineClearDelay(result, tspin , elapsed) => {
+            TetrisGameState::LineClearDelay(result, elapsed) => {
                 *elapsed += 1;
                 if *elapsed >= self.config.line_clear_delay {
-                    let mut attack = ATTACK_TABLE[result.lines_cleared as usize - 1];
-                    if *tspin == TspinType::Full {
-                        attack *= TSPIN_MULTIPLIER;
-                    }
-                    if result.b2b_bonus {
-                        attack += B2B_BONUS;
+                    let attack = self.config.attack.attack(result);
+                    if attack > 0 {
+                        events.push(TetrisGameEvent::Attack(attack));
                     }
                     if attack > self.garbage_pending {
                         events.push(TetrisGameEvent::GarbageSent(attack - self.garbage_pending));
@@ -194,21 +162,180 @@ impl TetrisGame {
                     events.push(TetrisGameEvent::PieceSpawned {
                         queued_piece: piece
                     });
-                    let piece = Piece::spawn(&self.board, piece);
-                    if !self.board.piece_fits(piece) {
-                        self.state = TetrisGameState::GameOver;
-                        events.push(TetrisGameEvent::GameOver);
-                    } else {
-                        self.state = TetrisGameState::PieceFalling(piece);
-                    }
+                    self.spawn_piece(piece, &mut events);
                 }
             }
             TetrisGameState::GameOver => {}
         }
+        self.prev_inputs = inputs;
         
         events
     }
 
+    fn update_piece(
+        &mut self, mut piece: Piece, inputs: EnumSet<TetrisInput>,
+        garbage_rng: &mut (impl Rng + ?Sized), events: &mut Vec<TetrisGameEvent>
+    ) {
+        if inputs.contains(TetrisInput::Hold) && !self.held {
+            self.held = true;
+            if let Some(kind) = self.board.hold.replace(piece.kind) {
+                self.spawn_piece(kind, events);
+            } else {
+                self.state = TetrisGameState::SpawnDelay(0);
+            }
+            return;
+        }
+
+        let grounded = !piece.clone().soft_drop(&self.board);
+        let mut moved = false;
+        if inputs.contains(TetrisInput::Left) != inputs.contains(TetrisInput::Right) {
+            let dir = if inputs.contains(TetrisInput::Left) {
+                TetrisInput::Left
+            } else {
+                TetrisInput::Right
+            };
+            let prev_dir = if self.prev_inputs.contains(TetrisInput::Left) != self.prev_inputs.contains(TetrisInput::Right) {
+                Some(if self.prev_inputs.contains(TetrisInput::Left) {
+                    TetrisInput::Left
+                } else {
+                    TetrisInput::Right
+                })
+            } else {
+                None
+            };
+            if Some(dir) != prev_dir {
+                self.das_timer = 0;
+            }
+
+            if self.das_timer == 0 {
+                moved |= if dir == TetrisInput::Left {
+                    piece.move_left(&self.board)
+                } else {
+                    piece.move_right(&self.board)
+                };
+            }
+            if self.das_timer == self.config.das {
+                loop {
+                    if self.arr_timer == 0 {
+                        let success = if dir == TetrisInput::Left {
+                            piece.move_left(&self.board)
+                        } else {
+                            piece.move_right(&self.board)
+                        };
+                        if !success {
+                            break;
+                        }
+                        moved = true;
+                    }
+                    if self.arr_timer < self.config.arr {
+                        break;
+                    }
+                    self.arr_timer -= self.config.arr;
+                }
+                self.arr_timer += 1;
+            } else {
+                self.das_timer += 1;
+            }
+        }
+
+        match self.config.rotate_180 {
+            Some(kicks) if inputs.contains(TetrisInput::Rot180) => {
+                moved |= piece.turn_180(&self.board, kicks);
+            }
+            _ => if inputs.contains(TetrisInput::RotLeft) != inputs.contains(TetrisInput::RotRight) {
+                moved |= if inputs.contains(TetrisInput::RotLeft) {
+                    piece.turn_left(&self.board)
+                } else {
+                    piece.turn_right(&self.board)
+                };
+            }
+        }
+
+        if moved && grounded && self.lock_resets < self.config.lock_resets {
+            self.lock_resets += 1;
+            self.lock_timer = 0;
+        }
+
+        let mut gravity = self.gravity();
+        if inputs.contains(TetrisInput::SoftDrop) {
+            gravity = (gravity * self.config.soft_drop_factor).max(1.0);
+        }
+        if gravity >= 20.0 {
+            piece.sonic_drop(&self.board);
+            self.gravity_progress = 0.0;
+        } else {
+            self.gravity_progress += gravity;
+            while self.gravity_progress >= 1.0 {
+                self.gravity_progress -= 1.0;
+                if !piece.soft_drop(&self.board) {
+                    self.gravity_progress = 0.0;
+                }
+            }
+        }
+        if piece.y < self.lowest_y {
+            self.lowest_y = piece.y;
+            self.lock_resets = 0;
+            self.lock_timer = 0;
+        }
+
+        if inputs.contains(TetrisInput::HardDrop) {
+            piece.sonic_drop(&self.board);
+            self.lock(piece, garbage_rng, events);
+            return;
+        }
+        if let Some(lock_delay) = self.config.lock_delay {
+            if !piece.clone().soft_drop(&self.board) {
+                self.lock_timer += 1;
+                if self.lock_timer > lock_delay {
+                    events.push(TetrisGameEvent::PieceAutoLocked);
+                    self.lock(piece, garbage_rng, events);
+                    return;
+                }
+            }
+        }
+        self.state = TetrisGameState::PieceFalling(piece);
+    }
+
+    fn spawn_piece(&mut self, kind: PieceType, events: &mut Vec<TetrisGameEvent>) {
+        let piece = Piece::spawn(&self.board, kind, self.config.rotation_system, self.config.spin_rule);
+        if !self.board.piece_fits(piece) {
+            self.state = TetrisGameState::GameOver;
+            events.push(TetrisGameEvent::GameOver);
+        } else {
+            self.state = TetrisGameState::PieceFalling(piece);
+            self.gravity_progress = 0.0;
+            self.lock_timer = 0;
+            self.lock_resets = 0;
+            self.lowest_y = piece.y;
+        }
+    }
+
+    fn lock(&mut self, piece: Piece, garbage_rng: &mut (impl Rng + ?Sized), events: &mut Vec<TetrisGameEvent>) {
+        let result = self.board.lock_piece(piece);
+        events.push(TetrisGameEvent::PieceLocked(result));
+        self.held = false;
+        self.lines += result.lines_cleared as u32;
+
+        if result.lines_cleared > 0 {
+            self.state = TetrisGameState::LineClearDelay(result, 0);
+        } else if result.block_out {
+            self.state = TetrisGameState::GameOver;
+            events.push(TetrisGameEvent::GameOver);
+        } else if !self.apply_garbage(garbage_rng, events) {
+            self.state = TetrisGameState::SpawnDelay(0);
+        }
+    }
+
+    /// Cells per frame the piece falls at the current level.
+    fn gravity(&self) -> f32 {
+        let level = self.get_level() as usize;
+        self.config.gravity
+            .get(level)
+            .or_else(|| self.config.gravity.last())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
     fn apply_garbage(&mut self, garbage_rng: &mut (impl Rng + ?Sized), events: &mut Vec<TetrisGameEvent>) -> bool {
         if self.garbage_pending > 0 {
             events.push(TetrisGameEvent::GarbageAdded(self.garbage_pending));
@@ -217,7 +344,7 @@ impl TetrisGame {
                 let hole = if let Some(&prev) = holes.last() {
                     if garbage_rng.gen_ratio(3, 10) {
                         let mut hole = prev;
-                        while hole != prev {
+                        while hole == prev {
                             hole = garbage_rng.gen_range(0, 10);
                         }
                         hole
@@ -254,6 +381,11 @@ impl TetrisGame {
         self.queue.get_queue()
     }
 
+    /// See `PieceQueue::bag_state`.
+    pub fn get_bag_state(&self) -> Option<EnumSet<PieceType>> {
+        self.queue.bag_state()
+    }
+
     pub fn get_config(&self) -> &TetrisGameConfig {
         &self.config
     }
@@ -265,4 +397,13 @@ impl TetrisGame {
     pub fn get_pending_garbage(&self) -> u32 {
         self.garbage_pending
     }
+
+    /// Lines cleared so far.
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn get_level(&self) -> u32 {
+        self.lines / self.config.lines_per_level.max(1)
+    }
 }
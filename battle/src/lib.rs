@@ -1,8 +1,34 @@
 use rand::prelude::*;
+use rand::rngs::StdRng;
 use enumset::EnumSet;
 
 mod game;
+mod driver;
+mod replay;
+mod timeline;
 pub use game::*;
+pub use driver::*;
+pub use replay::*;
+pub use timeline::*;
+
+/// Piece and garbage RNGs for a battle, all derived from one seed.
+/// Both players get the same pieces.
+pub struct BattleRng {
+    pub p1: StdRng,
+    pub p2: StdRng,
+    pub garbage: StdRng
+}
+
+impl BattleRng {
+    pub fn from_seed(seed: u64) -> Self {
+        let p1 = StdRng::seed_from_u64(seed);
+        Self {
+            p2: p1.clone(),
+            p1,
+            garbage: StdRng::seed_from_u64(!seed)
+        }
+    }
+}
 
 pub struct TetrisBattle {
     p1: TetrisGame,
@@ -22,6 +48,14 @@ impl TetrisBattle {
         }
     }
 
+    pub fn p1(&self) -> &TetrisGame {
+        &self.p1
+    }
+
+    pub fn p2(&self) -> &TetrisGame {
+        &self.p2
+    }
+
     pub fn update(
         &mut self,
         p1_inputs: EnumSet<TetrisInput>,
@@ -0,0 +1,412 @@
+use std::io::{Read, Write};
+use std::collections::VecDeque;
+use std::fmt;
+
+use minotetris::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use enumset::EnumSet;
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+/// A recorded game, stored as JSON.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Replay {
+    Battle(Box<BattleReplay>),
+    Bot(Box<BotReplay>)
+}
+
+impl Replay {
+    pub fn write(&self, writer: impl Write) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, self)
+    }
+
+    pub fn read(reader: impl Read) -> serde_json::Result<Self> {
+        serde_json::from_reader(reader)
+    }
+
+    /// Plays the replay back and checks that it produces the recorded events and final boards.
+    pub fn verify(&self) -> Result<(), ReplayError> {
+        match self {
+            Self::Battle(replay) => replay.verify(),
+            Self::Bot(replay) => replay.verify()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The events played back differ from the recording, starting at frame `at` of a
+    /// battle or placement `at` of a bot game. `player` is 1 or 2.
+    EventMismatch {
+        player: u32,
+        at: u32
+    },
+    BoardMismatch {
+        player: u32
+    },
+    /// The placement isn't the current or held piece, or isn't resting on the board.
+    InvalidPlacement(u32)
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::EventMismatch { player, at } => write!(f, "player {} events differ at {}", player, at),
+            Self::BoardMismatch { player } => write!(f, "player {} final board differs", player),
+            Self::InvalidPlacement(index) => write!(f, "placement {} can't be played", index)
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FrameEvent {
+    pub frame: u32,
+    pub event: TetrisGameEvent
+}
+
+/// One side of a recorded battle.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerReplay {
+    pub config: TetrisGameConfig,
+    /// Keys held on each frame.
+    pub inputs: Vec<EnumSet<TetrisInput>>,
+    pub events: Vec<FrameEvent>,
+    /// Board at the end of the recording.
+    pub board: Board
+}
+
+impl PlayerReplay {
+    fn new(config: TetrisGameConfig) -> Self {
+        Self {
+            config,
+            inputs: Vec::new(),
+            events: Vec::new(),
+            board: Board::new()
+        }
+    }
+
+    fn record(&mut self, inputs: EnumSet<TetrisInput>, events: &[TetrisGameEvent]) {
+        let frame = self.inputs.len() as u32;
+        self.inputs.push(inputs);
+        self.events.extend(events.iter().map(|event| FrameEvent {
+            frame,
+            event: event.clone()
+        }));
+    }
+}
+
+/// Per-frame inputs of a `TetrisBattle`, which replays it exactly given the seed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BattleReplay {
+    /// Seed passed to `BattleRng::from_seed`.
+    pub seed: u64,
+    pub p1: PlayerReplay,
+    pub p2: PlayerReplay
+}
+
+impl BattleReplay {
+    pub fn new(seed: u64, p1_config: TetrisGameConfig, p2_config: TetrisGameConfig) -> Self {
+        Self {
+            seed,
+            p1: PlayerReplay::new(p1_config),
+            p2: PlayerReplay::new(p2_config)
+        }
+    }
+
+    /// Records the inputs passed to `TetrisBattle::update` and the events it returned.
+    pub fn record(
+        &mut self,
+        p1_inputs: EnumSet<TetrisInput>,
+        p1_events: &[TetrisGameEvent],
+        p2_inputs: EnumSet<TetrisInput>,
+        p2_events: &[TetrisGameEvent]
+    ) {
+        self.p1.record(p1_inputs, p1_events);
+        self.p2.record(p2_inputs, p2_events);
+    }
+
+    /// Stores the final boards of the battle.
+    pub fn finish(&mut self, battle: &TetrisBattle) {
+        self.p1.board = battle.p1().get_board().clone();
+        self.p2.board = battle.p2().get_board().clone();
+    }
+
+    pub fn frames(&self) -> usize {
+        self.p1.inputs.len().min(self.p2.inputs.len())
+    }
+
+    fn verify(&self) -> Result<(), ReplayError> {
+        let mut playback = BattlePlayback::new(self);
+        let mut p1_events = Vec::new();
+        let mut p2_events = Vec::new();
+        while let Some((p1, p2)) = playback.step() {
+            let frame = playback.frame() as u32 - 1;
+            p1_events.extend(p1.into_iter().map(|event| FrameEvent { frame, event }));
+            p2_events.extend(p2.into_iter().map(|event| FrameEvent { frame, event }));
+        }
+        let players = [
+            (1, &self.p1, &p1_events, playback.battle().p1()),
+            (2, &self.p2, &p2_events, playback.battle().p2())
+        ];
+        for &(player, replay, events, game) in &players {
+            let mismatch = replay.events
+                .iter()
+                .zip(events)
+                .find(|(recorded, played)| recorded != played)
+                .map(|(recorded, played)| recorded.frame.min(played.frame));
+            let mismatch = mismatch.or_else(|| match replay.events.len().cmp(&events.len()) {
+                std::cmp::Ordering::Less => Some(events[replay.events.len()].frame),
+                std::cmp::Ordering::Greater => Some(replay.events[events.len()].frame),
+                std::cmp::Ordering::Equal => None
+            });
+            if let Some(at) = mismatch {
+                return Err(ReplayError::EventMismatch { player, at });
+            }
+            if !same_board(&replay.board, game.get_board()) {
+                return Err(ReplayError::BoardMismatch { player });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plays a `BattleReplay` back one frame at a time.
+pub struct BattlePlayback<'a> {
+    replay: &'a BattleReplay,
+    battle: TetrisBattle,
+    rng: BattleRng,
+    frame: usize
+}
+
+impl<'a> BattlePlayback<'a> {
+    pub fn new(replay: &'a BattleReplay) -> Self {
+        let mut rng = BattleRng::from_seed(replay.seed);
+        let battle = TetrisBattle::new(
+            replay.p1.config.clone(),
+            &mut rng.p1,
+            replay.p2.config.clone(),
+            &mut rng.p2
+        );
+        Self {
+            replay,
+            battle,
+            rng,
+            frame: 0
+        }
+    }
+
+    pub fn battle(&self) -> &TetrisBattle {
+        &self.battle
+    }
+
+    /// Number of frames played so far.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Plays the next frame, or returns `None` at the end of the replay.
+    pub fn step(&mut self) -> Option<(Vec<TetrisGameEvent>, Vec<TetrisGameEvent>)> {
+        if self.frame >= self.replay.frames() {
+            return None;
+        }
+        let events = self.battle.update(
+            self.replay.p1.inputs[self.frame],
+            &mut self.rng.p1,
+            self.replay.p2.inputs[self.frame],
+            &mut self.rng.p2,
+            &mut self.rng.garbage
+        );
+        self.frame += 1;
+        Some(events)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Placement {
+    pub piece: Piece,
+    pub uses_hold: bool,
+    /// Holes of the garbage that arrived after the piece locked, in `Board::add_garbage` order.
+    pub garbage: Vec<i32>,
+    pub events: Vec<TetrisGameEvent>
+}
+
+/// The pieces a bot placed in a game without a `TetrisGame`, such as in `stats` or `gui`.
+/// The queue is rebuilt from the seed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BotReplay {
+    /// Seed of the piece RNG.
+    pub seed: u64,
+    /// Pieces shown after the current one.
+    pub queue: u32,
+    pub randomizer: RandomizerKind,
+    pub rotation_system: RotationSystem,
+    pub attack: AttackRules,
+    /// Holes of the garbage on the board before the first piece.
+    pub garbage: Vec<i32>,
+    pub placements: Vec<Placement>,
+    /// Board at the end of the recording.
+    pub board: Board
+}
+
+impl BotReplay {
+    pub fn new(
+        seed: u64,
+        queue: u32,
+        randomizer: RandomizerKind,
+        rotation_system: RotationSystem,
+        attack: AttackRules,
+        garbage: Vec<i32>
+    ) -> Self {
+        let mut board = Board::new();
+        board.add_garbage(&garbage);
+        Self {
+            seed,
+            queue,
+            randomizer,
+            rotation_system,
+            attack,
+            garbage,
+            placements: Vec::new(),
+            board
+        }
+    }
+
+    /// Records a piece locking with `lock`, sending `attack` lines and followed by `garbage`.
+    pub fn record(&mut self, piece: Piece, uses_hold: bool, lock: LockResult, attack: u32, garbage: Vec<i32>) {
+        self.placements.push(Placement {
+            piece,
+            uses_hold,
+            events: placement_events(lock, attack, &garbage),
+            garbage
+        });
+    }
+
+    /// Stores the final board of the game.
+    pub fn finish(&mut self, board: &Board) {
+        self.board = board.clone();
+    }
+
+    fn verify(&self) -> Result<(), ReplayError> {
+        let mut playback = BotPlayback::<u16>::new(self);
+        loop {
+            let at = playback.placed() as u32;
+            match playback.step()? {
+                Some(events) if events != self.placements[at as usize].events => {
+                    return Err(ReplayError::EventMismatch { player: 1, at });
+                }
+                Some(_) => {}
+                None => break
+            }
+        }
+        if !same_board(&self.board, playback.board()) {
+            return Err(ReplayError::BoardMismatch { player: 1 });
+        }
+        Ok(())
+    }
+}
+
+fn placement_events(lock: LockResult, attack: u32, garbage: &[i32]) -> Vec<TetrisGameEvent> {
+    let mut events = vec![TetrisGameEvent::PieceLocked(lock)];
+    if attack > 0 {
+        events.push(TetrisGameEvent::Attack(attack));
+    }
+    if !garbage.is_empty() {
+        events.push(TetrisGameEvent::GarbageAdded(garbage.len() as u32));
+    }
+    if lock.block_out {
+        events.push(TetrisGameEvent::GameOver);
+    }
+    events
+}
+
+/// Plays a `BotReplay` back one piece at a time.
+pub struct BotPlayback<'a, R=u16> {
+    replay: &'a BotReplay,
+    board: Board<R>,
+    queue: PieceQueue,
+    rng: StdRng,
+    current: PieceType,
+    placed: usize
+}
+
+impl<'a, R: Row> BotPlayback<'a, R> {
+    pub fn new(replay: &'a BotReplay) -> Self {
+        let mut rng = StdRng::seed_from_u64(replay.seed);
+        let mut queue = PieceQueue::new(replay.queue as usize, replay.randomizer.build(), &mut rng);
+        let current = queue.next(&mut rng);
+        let mut board = Board::new();
+        board.add_garbage(&replay.garbage);
+        Self {
+            replay,
+            board,
+            queue,
+            rng,
+            current,
+            placed: 0
+        }
+    }
+
+    pub fn board(&self) -> &Board<R> {
+        &self.board
+    }
+
+    /// The piece to be placed or held next.
+    pub fn current(&self) -> PieceType {
+        self.current
+    }
+
+    pub fn queue(&self) -> &VecDeque<PieceType> {
+        self.queue.get_queue()
+    }
+
+    /// Number of pieces placed so far.
+    pub fn placed(&self) -> usize {
+        self.placed
+    }
+
+    pub fn next_placement(&self) -> Option<&'a Placement> {
+        self.replay.placements.get(self.placed)
+    }
+
+    /// Places the next piece and returns the events it caused, or `None` at the end of the replay.
+    pub fn step(&mut self) -> Result<Option<Vec<TetrisGameEvent>>, ReplayError> {
+        let placement = match self.next_placement() {
+            Some(placement) => placement,
+            None => return Ok(None)
+        };
+        let invalid = ReplayError::InvalidPlacement(self.placed as u32);
+        let mut board = self.board.clone();
+        let mut kind = self.current;
+        if placement.uses_hold {
+            kind = match board.hold.replace(kind) {
+                Some(held) => held,
+                None => self.queue.next(&mut self.rng)
+            };
+        }
+        let piece = placement.piece;
+        if piece.kind != kind || !board.piece_fits(piece) || piece.clone().soft_drop(&board) {
+            return Err(invalid);
+        }
+        let mut lock = board.lock_piece(piece);
+        if board.add_garbage(&placement.garbage) {
+            lock.block_out = true;
+        }
+        let attack = self.replay.attack.attack(&lock);
+        self.board = board;
+        self.current = self.queue.next(&mut self.rng);
+        self.placed += 1;
+        Ok(Some(placement_events(lock, attack, &placement.garbage)))
+    }
+}
+
+fn same_board<R: Row, S: Row>(a: &Board<R>, b: &Board<S>) -> bool {
+    let same_rows = a.rows()
+        .iter()
+        .zip(b.rows())
+        .all(|(a, b)| (0..10).all(|x| a.get(x) == b.get(x)));
+    same_rows && a.hold == b.hold && a.combo == b.combo && a.b2b == b.b2b
+}
@@ -0,0 +1,184 @@
+use minotetris::*;
+
+use crate::*;
+
+/// What one player sees at a point of a replay.
+pub struct PlayerView {
+    pub board: Board<ColoredRow>,
+    pub piece: Option<Piece>,
+    pub queue: Vec<PieceType>,
+    pub garbage_pending: u32
+}
+
+/// Every point of a replay, played back up front so it can be stepped through
+/// backward as quickly as forward.
+pub struct Timeline {
+    /// What each player sees at every frame of a battle or after every piece of a bot game.
+    steps: Vec<Vec<PlayerView>>,
+    /// Step each move starts at, where a move ends when any player locks a piece.
+    moves: Vec<usize>,
+    /// The step each event happened at, for each player.
+    events: Vec<Vec<(usize, TetrisGameEvent)>>,
+    position: usize,
+    pub rotation_system: RotationSystem,
+    /// Why a bot replay stopped before its last placement, if it did.
+    pub error: Option<ReplayError>
+}
+
+impl Timeline {
+    pub fn new(replay: &Replay) -> Self {
+        let mut timeline = Self {
+            steps: Vec::new(),
+            moves: vec![0],
+            events: Vec::new(),
+            position: 0,
+            rotation_system: RotationSystem::Srs,
+            error: None
+        };
+        match replay {
+            Replay::Battle(replay) => timeline.load_battle(replay),
+            Replay::Bot(replay) => timeline.load_bot(replay)
+        }
+        timeline
+    }
+
+    fn load_battle(&mut self, replay: &BattleReplay) {
+        self.rotation_system = replay.p1.config.rotation_system;
+        self.events = vec![Vec::new(), Vec::new()];
+        let mut playback = BattlePlayback::new(replay);
+        let view = |game: &TetrisGame| PlayerView {
+            board: colored(game.get_board()),
+            piece: match game.get_state() {
+                TetrisGameState::PieceFalling(piece) => Some(*piece),
+                _ => None
+            },
+            queue: game.get_queue().iter().copied().collect(),
+            garbage_pending: game.get_pending_garbage()
+        };
+        self.steps.push(vec![view(playback.battle().p1()), view(playback.battle().p2())]);
+        while let Some((p1_events, p2_events)) = playback.step() {
+            let step = playback.frame();
+            let locked = p1_events
+                .iter()
+                .chain(&p2_events)
+                .any(|event| matches!(event, TetrisGameEvent::PieceLocked(_)));
+            if locked {
+                self.moves.push(step);
+            }
+            for (log, events) in self.events.iter_mut().zip(vec![p1_events, p2_events]) {
+                log.extend(events.into_iter().map(|event| (step, event)));
+            }
+            self.steps.push(vec![view(playback.battle().p1()), view(playback.battle().p2())]);
+        }
+    }
+
+    fn load_bot(&mut self, replay: &BotReplay) {
+        self.rotation_system = replay.rotation_system;
+        self.events = vec![Vec::new()];
+        let mut playback = BotPlayback::<ColoredRow>::new(replay);
+        let view = |playback: &BotPlayback<ColoredRow>| PlayerView {
+            board: playback.board().clone(),
+            piece: playback.next_placement().map(|placement| placement.piece),
+            queue: playback.queue().iter().copied().collect(),
+            garbage_pending: 0
+        };
+        self.steps.push(vec![view(&playback)]);
+        loop {
+            match playback.step() {
+                Ok(Some(events)) => {
+                    let step = playback.placed();
+                    self.moves.push(step);
+                    self.events[0].extend(events.into_iter().map(|event| (step, event)));
+                    self.steps.push(vec![view(&playback)]);
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    self.error = Some(err);
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.steps[0].len()
+    }
+
+    /// What each player sees at the current step.
+    pub fn views(&self) -> &[PlayerView] {
+        &self.steps[self.position]
+    }
+
+    /// Events of `player` with the step each happened at.
+    pub fn events(&self, player: usize) -> &[(usize, TetrisGameEvent)] {
+        &self.events[player]
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn last_step(&self) -> usize {
+        self.steps.len() - 1
+    }
+
+    /// Number of moves before the current step.
+    pub fn current_move(&self) -> usize {
+        self.moves.iter().filter(|&&step| step <= self.position).count() - 1
+    }
+
+    pub fn last_move(&self) -> usize {
+        self.moves.len() - 1
+    }
+
+    /// Goes to `step`, or the last step if it is past the end.
+    pub fn seek(&mut self, step: usize) {
+        self.position = step.min(self.last_step());
+    }
+
+    /// Goes to the start of move `mv`, or the last move if it is past the end.
+    pub fn seek_move(&mut self, mv: usize) {
+        self.seek(self.moves[mv.min(self.last_move())]);
+    }
+
+    /// Goes to the start of the next move, or the last step if this is the last move.
+    pub fn next_move(&mut self) {
+        let next = self.moves.iter().copied().find(|&step| step > self.position);
+        self.seek(next.unwrap_or(self.last_step()));
+    }
+
+    /// Goes to the start of the move before the current step.
+    pub fn prev_move(&mut self) {
+        let prev = self.moves.iter().copied().rev().find(|&step| step < self.position);
+        self.seek(prev.unwrap_or(0));
+    }
+
+    /// Goes one step forward. Returns `false` if already at the last step.
+    pub fn step_forward(&mut self) -> bool {
+        if self.position == self.last_step() {
+            return false;
+        }
+        self.position += 1;
+        true
+    }
+
+    pub fn step_back(&mut self) {
+        self.position = self.position.saturating_sub(1);
+    }
+}
+
+/// Copies a board from a `TetrisGame`, which doesn't keep track of the colors of pieces.
+fn colored(board: &Board) -> Board<ColoredRow> {
+    let mut rows = [ColoredRow::default(); 40];
+    for (colored, row) in rows.iter_mut().zip(board.rows()) {
+        for x in 0..10 {
+            colored.set(x, row.cell_type(x));
+        }
+    }
+    let mut colored = Board::new();
+    colored.set_field(rows);
+    colored.hold = board.hold;
+    colored.combo = board.combo;
+    colored.b2b = board.b2b;
+    colored
+}
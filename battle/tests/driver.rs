@@ -0,0 +1,56 @@
+use std::convert::TryFrom;
+
+use minotetris::*;
+use battle::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+fn config() -> TetrisGameConfig {
+    TetrisGameConfig {
+        randomizer: RandomizerKind::Sequence(PieceSequence::try_from(vec![PieceType::T, PieceType::I]).unwrap()),
+        ..TetrisGameConfig::default()
+    }
+}
+
+#[test]
+fn driver_lands_on_target() {
+    let config = config();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut game = TetrisGame::new(config.clone(), &mut rng);
+    let board = Board::new();
+    let mut target = Piece::spawn(&board, PieceType::T, config.rotation_system, config.spin_rule);
+    target.turn_right(&board);
+    for _ in 0..3 {
+        target.move_left(&board);
+    }
+    target.sonic_drop(&board);
+    let path = [TetrisInput::RotRight, TetrisInput::Left, TetrisInput::Left, TetrisInput::Left];
+    let mut driver = InputDriver::new(target, false, &path, &board, &config).unwrap();
+    for _ in 0..200 {
+        let inputs = driver.update(&game);
+        game.update(inputs, &mut rng, &mut StdRng::seed_from_u64(1));
+        if driver.finished() {
+            break;
+        }
+    }
+    assert!(matches!(driver.outcome(), Some(DriveOutcome::Landed)));
+    for (x, y) in target.cells().iter().copied() {
+        assert!(game.get_board().occupied(x, y));
+    }
+}
+
+#[test]
+fn rotate_180_needs_kicks() {
+    let mut config = config();
+    let board = Board::new();
+    let target = Piece::spawn(&board, PieceType::T, config.rotation_system, config.spin_rule);
+    let path = [TetrisInput::Rot180];
+    assert_eq!(InputDriver::new(target, false, &path, &board, &config).err(), Some(DriveError::NoRotate180));
+    config.rotate_180 = Some(Kicks180::SrsPlus);
+    assert!(InputDriver::new(target, false, &path, &board, &config).is_ok());
+    let path = [TetrisInput::HardDrop];
+    assert_eq!(
+        InputDriver::new(target, false, &path, &board, &config).err(),
+        Some(DriveError::UnexpectedInput(TetrisInput::HardDrop))
+    );
+}
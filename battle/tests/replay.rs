@@ -0,0 +1,139 @@
+use minotetris::*;
+use battle::*;
+use enumset::EnumSet;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Mashes random keys in a battle for up to `frames` frames.
+fn record_battle(seed: u64, frames: u32) -> BattleReplay {
+    let config = TetrisGameConfig {
+        gravity: vec![0.05],
+        lock_delay: Some(30),
+        ..TetrisGameConfig::default()
+    };
+    let mut rng = BattleRng::from_seed(seed);
+    let mut battle = TetrisBattle::new(config.clone(), &mut rng.p1, config.clone(), &mut rng.p2);
+    let mut replay = BattleReplay::new(seed, config.clone(), config);
+    let mut keys = StdRng::seed_from_u64(!seed);
+    for _ in 0..frames {
+        let p1_inputs = EnumSet::from_u8(keys.gen::<u8>() & keys.gen::<u8>());
+        let p2_inputs = EnumSet::from_u8(keys.gen::<u8>() & keys.gen::<u8>());
+        let (p1_events, p2_events) = battle.update(
+            p1_inputs,
+            &mut rng.p1,
+            p2_inputs,
+            &mut rng.p2,
+            &mut rng.garbage
+        );
+        replay.record(p1_inputs, &p1_events, p2_inputs, &p2_events);
+        let game_over = p1_events.iter().chain(&p2_events).any(|e| *e == TetrisGameEvent::GameOver);
+        if game_over {
+            break;
+        }
+    }
+    replay.finish(&battle);
+    replay
+}
+
+/// Where `piece` would land with the lowest stack, trying every rotation and column.
+fn lowest_placement(board: &Board, kind: PieceType) -> Piece {
+    let mut best: Option<(i32, Piece)> = None;
+    for r in 0..4 {
+        let mut piece = Piece::spawn(board, kind, RotationSystem::Srs, SpinRule::TSpin);
+        for _ in 0..r {
+            piece.turn_right(board);
+        }
+        while piece.move_left(board) {}
+        loop {
+            let mut landed = piece;
+            landed.sonic_drop(board);
+            let mut after = board.clone();
+            after.lock_piece(landed);
+            let height = after.column_heights().iter().copied().max().unwrap();
+            if best.is_none_or(|(best, _)| height < best) {
+                best = Some((height, landed));
+            }
+            if !piece.move_right(board) {
+                break;
+            }
+        }
+    }
+    best.unwrap().1
+}
+
+/// Plays a game with garbage arriving every few pieces, recording it like `stats` does.
+fn record_bot_game(seed: u64, pieces: u32) -> BotReplay {
+    let attack = AttackRules::default();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut queue = PieceQueue::new(4, RandomizerKind::Bag7.build(), &mut rng);
+    let mut current = queue.next(&mut rng);
+    let mut board = Board::new();
+    let mut replay = BotReplay::new(seed, 4, RandomizerKind::Bag7, RotationSystem::Srs, attack.clone(), Vec::new());
+    for i in 0..pieces {
+        let uses_hold = i % 7 == 3;
+        let mut kind = current;
+        if uses_hold {
+            kind = match board.hold.replace(kind) {
+                Some(held) => held,
+                None => queue.next(&mut rng)
+            };
+        }
+        let piece = lowest_placement(&board, kind);
+        let lock = board.lock_piece(piece);
+        let garbage = if i % 5 == 4 { vec![3; 2] } else { Vec::new() };
+        board.add_garbage(&garbage);
+        replay.record(piece, uses_hold, lock, attack.attack(&lock), garbage);
+        current = queue.next(&mut rng);
+    }
+    replay.finish(&board);
+    replay
+}
+
+fn round_trip(replay: Replay) -> Replay {
+    let mut bytes = Vec::new();
+    replay.write(&mut bytes).unwrap();
+    Replay::read(&bytes[..]).unwrap()
+}
+
+#[test]
+fn battle_replay_verifies() {
+    let replay = record_battle(5, 3000);
+    assert!(replay.p1.events.iter().any(|e| matches!(e.event, TetrisGameEvent::PieceLocked(_))));
+    let replay = round_trip(Replay::Battle(Box::new(replay)));
+    assert_eq!(replay.verify(), Ok(()));
+}
+
+#[test]
+fn tampered_battle_replay_fails() {
+    let mut replay = record_battle(5, 3000);
+    replay.seed += 1;
+    assert!(Replay::Battle(Box::new(replay)).verify().is_err());
+
+    let mut replay = record_battle(5, 3000);
+    replay.p2.board.hold = if replay.p2.board.hold == Some(PieceType::T) {
+        Some(PieceType::O)
+    } else {
+        Some(PieceType::T)
+    };
+    assert_eq!(Replay::Battle(Box::new(replay)).verify(), Err(ReplayError::BoardMismatch { player: 2 }));
+}
+
+#[test]
+fn bot_replay_verifies() {
+    let replay = record_bot_game(9, 30);
+    assert!(replay.placements.iter().any(|p| !p.garbage.is_empty()));
+    let replay = round_trip(Replay::Bot(Box::new(replay)));
+    assert_eq!(replay.verify(), Ok(()));
+}
+
+#[test]
+fn tampered_bot_replay_fails() {
+    let mut replay = record_bot_game(9, 30);
+    replay.placements[3].uses_hold = !replay.placements[3].uses_hold;
+    assert!(Replay::Bot(Box::new(replay)).verify().is_err());
+
+    let mut replay = record_bot_game(9, 30);
+    let placement = replay.placements.iter_mut().find(|p| !p.garbage.is_empty()).unwrap();
+    placement.garbage.clear();
+    assert!(Replay::Bot(Box::new(replay)).verify().is_err());
+}
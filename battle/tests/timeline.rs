@@ -0,0 +1,140 @@
+use minotetris::*;
+use battle::*;
+use enumset::EnumSet;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Mashes random keys in a battle for `frames` frames.
+fn record_battle(seed: u64, frames: u32) -> BattleReplay {
+    let config = TetrisGameConfig {
+        gravity: vec![0.05],
+        lock_delay: Some(30),
+        ..TetrisGameConfig::default()
+    };
+    let mut rng = BattleRng::from_seed(seed);
+    let mut battle = TetrisBattle::new(config.clone(), &mut rng.p1, config.clone(), &mut rng.p2);
+    let mut replay = BattleReplay::new(seed, config.clone(), config);
+    let mut keys = StdRng::seed_from_u64(!seed);
+    for _ in 0..frames {
+        let p1_inputs = EnumSet::from_u8(keys.gen::<u8>() & keys.gen::<u8>());
+        let p2_inputs = EnumSet::from_u8(keys.gen::<u8>() & keys.gen::<u8>());
+        let (p1_events, p2_events) = battle.update(
+            p1_inputs,
+            &mut rng.p1,
+            p2_inputs,
+            &mut rng.p2,
+            &mut rng.garbage
+        );
+        replay.record(p1_inputs, &p1_events, p2_inputs, &p2_events);
+        let game_over = p1_events.iter().chain(&p2_events).any(|e| *e == TetrisGameEvent::GameOver);
+        if game_over {
+            break;
+        }
+    }
+    replay.finish(&battle);
+    replay
+}
+
+/// Drops every piece a few columns left of where it spawns.
+fn record_bot_game(seed: u64, pieces: u32) -> BotReplay {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut queue = PieceQueue::new(4, RandomizerKind::Bag7.build(), &mut rng);
+    let mut board = Board::new();
+    let attack = AttackRules::default();
+    let mut replay = BotReplay::new(seed, 4, RandomizerKind::Bag7, RotationSystem::Srs, attack.clone(), Vec::new());
+    for i in 0..pieces {
+        let mut piece = Piece::spawn(&board, queue.next(&mut rng), RotationSystem::Srs, SpinRule::TSpin);
+        for _ in 0..i % 4 {
+            piece.move_left(&board);
+        }
+        piece.sonic_drop(&board);
+        let lock = board.lock_piece(piece);
+        replay.record(piece, false, lock, attack.attack(&lock), Vec::new());
+    }
+    replay.finish(&board);
+    replay
+}
+
+#[test]
+fn jump_to_move() {
+    let replay = Replay::Battle(Box::new(record_battle(4, 600)));
+    let mut timeline = Timeline::new(&replay);
+    assert_eq!(timeline.players(), 2);
+    assert!(timeline.last_move() > 2);
+    for mv in 0..=timeline.last_move() {
+        timeline.seek_move(mv);
+        assert_eq!(timeline.current_move(), mv);
+        if mv > 0 {
+            let locked = timeline.events(0)
+                .iter()
+                .chain(timeline.events(1))
+                .any(|(step, event)| {
+                    *step == timeline.position() && matches!(event, TetrisGameEvent::PieceLocked(_))
+                });
+            assert!(locked);
+        }
+    }
+    timeline.seek_move(timeline.last_move() + 10);
+    assert_eq!(timeline.current_move(), timeline.last_move());
+
+    timeline.seek(0);
+    for mv in 1..=timeline.last_move() {
+        timeline.next_move();
+        assert_eq!(timeline.current_move(), mv);
+    }
+    timeline.next_move();
+    assert_eq!(timeline.position(), timeline.last_step());
+    timeline.prev_move();
+    timeline.prev_move();
+    assert_eq!(timeline.current_move(), timeline.last_move() - 1);
+}
+
+#[test]
+fn step_through_frames() {
+    let replay = record_battle(5, 300);
+    let mut timeline = Timeline::new(&Replay::Battle(Box::new(replay.clone())));
+    assert_eq!(timeline.last_step(), replay.frames());
+    timeline.step_back();
+    assert_eq!(timeline.position(), 0);
+
+    let mut playback = BattlePlayback::new(&replay);
+    while playback.step().is_some() {
+        assert!(timeline.step_forward());
+        assert_eq!(timeline.position(), playback.frame());
+        let games = [playback.battle().p1(), playback.battle().p2()];
+        for (view, game) in timeline.views().iter().zip(&games) {
+            assert_eq!(view.garbage_pending, game.get_pending_garbage());
+            assert!(view.queue.iter().eq(game.get_queue()));
+            for (y, row) in game.get_board().rows().iter().enumerate() {
+                for x in 0..10 {
+                    assert_eq!(view.board.occupied(x, y as i32), row.get(x as usize));
+                }
+            }
+        }
+    }
+    assert!(!timeline.step_forward());
+    assert_eq!(timeline.position(), timeline.last_step());
+    timeline.step_back();
+    assert_eq!(timeline.position(), timeline.last_step() - 1);
+}
+
+#[test]
+fn bot_game_steps_by_piece() {
+    let replay = record_bot_game(6, 12);
+    let mut timeline = Timeline::new(&Replay::Bot(Box::new(replay.clone())));
+    assert!(timeline.error.is_none());
+    assert_eq!(timeline.players(), 1);
+    assert_eq!(timeline.last_step(), 12);
+    assert_eq!(timeline.last_move(), 12);
+    timeline.seek_move(5);
+    assert_eq!(timeline.position(), 5);
+    assert_eq!(timeline.views()[0].piece, Some(replay.placements[5].piece));
+    timeline.step_forward();
+    assert_eq!(timeline.current_move(), 6);
+
+    let mut tampered = replay;
+    tampered.placements[8].piece.x += 20;
+    let timeline = Timeline::new(&Replay::Bot(Box::new(tampered)));
+    assert_eq!(timeline.error, Some(ReplayError::InvalidPlacement(8)));
+    assert_eq!(timeline.last_step(), 8);
+}
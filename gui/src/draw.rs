@@ -0,0 +1,150 @@
+use ggez::graphics;
+use ggez::nalgebra as na;
+use minotetris::*;
+
+pub const HOLD_WIDTH: i32 = 4;
+pub const HOLD_PADDING: i32 = 1;
+pub const BOARD_WIDTH: i32 = 10;
+pub const QUEUE_PADDING: i32 = 1;
+pub const QUEUE_WIDTH: i32 = 4;
+/// Width of a board with its hold and queue.
+pub const WIDTH: i32 = HOLD_WIDTH + HOLD_PADDING + BOARD_WIDTH + QUEUE_PADDING + QUEUE_WIDTH;
+pub const HEIGHT: i32 = 20;
+
+/// Size of everything drawn, in cells, which is scaled to fit the window.
+#[derive(Copy, Clone)]
+pub struct Scene {
+    pub width: i32,
+    pub height: i32,
+    /// Rotation system the pieces in hold and the queue are shown in.
+    pub rotation_system: RotationSystem
+}
+
+impl Scene {
+    pub fn cell_size(&self, ctx: &ggez::Context) -> f32 {
+        let (width, height) = graphics::drawable_size(ctx);
+        (width / self.width as f32).min(height / self.height as f32)
+    }
+
+    /// Bounds of the cell in column `x` and row `row`, counting rows down from the top.
+    pub fn cell(&self, ctx: &ggez::Context, x: i32, row: i32) -> graphics::Rect {
+        let (width, height) = graphics::drawable_size(ctx);
+        let cell_size = self.cell_size(ctx);
+        let start_x = (width - cell_size * self.width as f32) / 2.0;
+        let start_y = (height - cell_size * self.height as f32) / 2.0;
+        graphics::Rect::new(
+            start_x + x as f32 * cell_size,
+            start_y + row as f32 * cell_size,
+            cell_size,
+            cell_size
+        )
+    }
+
+    /// Draws a line of text a cell tall, starting at column `x` and row `row`.
+    pub fn draw_text(&self, ctx: &mut ggez::Context, text: &str, x: i32, row: i32) -> ggez::GameResult {
+        let bounds = self.cell(ctx, x, row);
+        let mut text = graphics::Text::new(text);
+        text.set_font(graphics::Font::default(), graphics::Scale::uniform(bounds.h * 0.8));
+        graphics::draw(ctx, &text, (na::Point2::new(bounds.x, bounds.y), graphics::WHITE))
+    }
+}
+
+/// Draws a board with its hold and queue, `x` columns from the left of the scene.
+pub fn draw_game(
+    ctx: &mut ggez::Context,
+    mesh: &mut graphics::MeshBuilder,
+    scene: Scene,
+    x: i32,
+    board: &Board<ColoredRow>,
+    piece: Option<Piece>,
+    queue: &[PieceType]
+) -> ggez::GameResult {
+    let board_x = x + HOLD_WIDTH + HOLD_PADDING;
+    for y in 0..20 {
+        for cell_x in 0..10 {
+            let cell = board.rows()[y as usize].cell_type(cell_x as usize);
+            draw_cell(ctx, mesh, scene, cell, false, board_x + cell_x, y)?;
+        }
+    }
+    if let Some(piece) = piece {
+        let mut shown = piece;
+        shown.x += board_x;
+        draw_piece(ctx, mesh, scene, shown, false)?;
+
+        let mut ghost = piece;
+        ghost.sonic_drop(board);
+        ghost.x += board_x;
+        draw_piece(ctx, mesh, scene, ghost, true)?;
+    }
+
+    let preview = |kind, x, y| Piece {
+        kind,
+        x,
+        y,
+        r: 0,
+        tspin: TspinType::None,
+        system: scene.rotation_system,
+        spin_rule: SpinRule::default()
+    };
+    if let Some(kind) = board.hold {
+        draw_piece(ctx, mesh, scene, preview(kind, x + 1, 17), false)?;
+    }
+    let queue_x = board_x + BOARD_WIDTH + QUEUE_PADDING + 1;
+    for (i, &kind) in queue.iter().enumerate() {
+        let y = (queue.len() - 1 - i) as i32 * 4 + 1;
+        draw_piece(ctx, mesh, scene, preview(kind, queue_x, y), false)?;
+    }
+    Ok(())
+}
+
+pub fn draw_piece(
+    ctx: &mut ggez::Context,
+    mesh: &mut graphics::MeshBuilder,
+    scene: Scene,
+    piece: Piece,
+    ghost: bool
+) -> ggez::GameResult {
+    for &(x, y) in &piece.cells() {
+        draw_cell(ctx, mesh, scene, piece.kind.cell(), ghost, x, y)?;
+    }
+    Ok(())
+}
+
+pub fn draw_cell(
+    ctx: &mut ggez::Context,
+    mesh: &mut graphics::MeshBuilder,
+    scene: Scene,
+    cell: CellType,
+    ghost: bool,
+    x: i32,
+    y: i32
+) -> ggez::GameResult {
+    let bounds = scene.cell(ctx, x, 19 - y);
+    let color = match cell {
+        CellType::Empty => (112, 128, 144),//(112, 128, 144),
+        CellType::Garbage => (112, 128, 144),
+        CellType::Solid => (105, 105, 105),
+        CellType::J => (0, 0, 255),
+        CellType::L => (255, 69, 0),
+        CellType::S => (0, 255, 0),
+        CellType::T => (138, 43, 226),
+        CellType::Z => (255, 0, 0),
+        CellType::I => (0, 255, 255),
+        CellType::O => (255, 215, 0)
+    };
+    let mut color: graphics::Color = color.into();
+    if ghost {
+        color.a = 0.5;
+    }
+    let mode = graphics::DrawMode::fill();
+    mesh.rectangle(mode, bounds, color);
+    if cell == CellType::Empty {
+        const BORDER: f32 = 0.1;
+        let mut bounds = bounds;
+        let border = bounds.w * BORDER;
+        bounds.translate([border / 2.0, border / 2.0]);
+        bounds.scale(1.0 - BORDER, 1.0 - BORDER);
+        mesh.rectangle(mode, bounds, graphics::BLACK);
+    }
+    Ok(())
+}
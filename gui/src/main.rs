@@ -10,24 +10,39 @@ use ggez::nalgebra as na;
 use minotetris::*;
 use minobot::pathfinder::PathfinderMove;
 use minobot::evaluator::StandardEvaluator;
-use minobot::bot::BotSettings;
+use minobot::bot::{BotSettings, ThinkBudget};
 use minobot::BotHandle;
+use battle::{Replay, BotReplay};
 use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+use rand::rngs::StdRng;
 
+mod draw;
+mod viewer;
+use draw::*;
+use viewer::Viewer;
 
 struct MainState {
     board: Board<ColoredRow>,
     queue: PieceQueue,
+    rng: StdRng,
     piece: Piece,
     bot: BotHandle,
     state: State,
     think_time: Duration,
-    move_time: Duration
+    budget: ThinkBudget,
+    move_time: Duration,
+    rotation_system: RotationSystem,
+    spin_rule: SpinRule,
+    rotate_180: Option<Kicks180>,
+    replay: BotReplay,
+    replay_path: Option<String>
 }
 
 enum State {
     Thinking(Instant),
-    Moving(VecDeque<PathfinderMove>, Instant),
+    /// The path left to play and whether the move uses hold.
+    Moving(VecDeque<PathfinderMove>, bool, Instant),
 }
 
 const OPTIONS_PATH: &'static str = "minobot_options.yaml";
@@ -37,8 +52,19 @@ struct Options {
     evaluator: StandardEvaluator,
     settings: BotSettings,
     think_time: u64,
+    /// Thinking budget for each move. The bot still waits `think_time` before moving.
+    #[serde(default)]
+    budget: ThinkBudget,
     move_time: u64,
-    queue: u32
+    queue: u32,
+    #[serde(default)]
+    randomizer: RandomizerKind,
+    /// Seed for the piece RNG. A random one is picked and printed if this is left out.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// File to write a replay of the game to when the window is closed.
+    #[serde(default)]
+    replay: Option<String>
 }
 
 impl Default for Options {
@@ -47,8 +73,12 @@ impl Default for Options {
             evaluator: StandardEvaluator::default(),
             settings: BotSettings::default(),
             think_time: 100,
+            budget: ThinkBudget::default(),
             move_time: 50,
-            queue: 5
+            queue: 5,
+            randomizer: RandomizerKind::default(),
+            seed: None,
+            replay: None
         }
     }
 }
@@ -87,14 +117,6 @@ impl From<serde_yaml::Error> for OptionsReadingError {
     }
 }
 
-const HOLD_WIDTH: i32 = 4;
-const HOLD_PADDING: i32 = 1;
-const BOARD_WIDTH: i32 = 10;
-const QUEUE_PADDING: i32 = 1;
-const QUEUE_WIDTH: i32 = 4;
-const WIDTH: i32 = HOLD_WIDTH + HOLD_PADDING + BOARD_WIDTH + QUEUE_PADDING + QUEUE_WIDTH;
-const HEIGHT: i32 = 20;
-
 impl MainState {
     fn new() -> ggez::GameResult<MainState> {
         let options = match Options::read() {
@@ -104,27 +126,50 @@ impl MainState {
                 Options::default()
             }
         };
-        let mut rng = rand::thread_rng();
+        let seed = options.seed.unwrap_or_else(rand::random);
+        println!("Seed: {}", seed);
+        let mut rng = StdRng::seed_from_u64(seed);
 
         let board = Board::<ColoredRow>::new();
-        let mut queue = PieceQueue::new(options.queue as usize, &mut rng);
-        let piece = Piece::spawn(&board, queue.next(&mut rng));
+        let mut queue = PieceQueue::new(options.queue as usize, options.randomizer.build(), &mut rng);
+        let rotation_system = options.settings.rotation_system;
+        let spin_rule = options.settings.spin_rule;
+        let piece = Piece::spawn(&board, queue.next(&mut rng), rotation_system, spin_rule);
 
+        let rotate_180 = options.settings.rotate_180;
+        let replay = BotReplay::new(
+            seed,
+            options.queue,
+            options.randomizer.clone(),
+            rotation_system,
+            options.settings.attack.clone(),
+            Vec::new()
+        );
         let bot = BotHandle::new(board.compress(), options.evaluator, options.settings);
         bot.add_piece(piece.kind);
         for &piece in queue.get_queue() {
             bot.add_piece(piece);
         }
-        bot.begin_thinking();
+        if let Some(bag) = queue.bag_state() {
+            bot.set_bag(bag);
+        }
+        bot.begin_thinking_with(options.budget.clone());
         
         Ok(MainState {
             board,
             queue,
+            rng,
             piece,
             bot,
             state: State::Thinking(Instant::now()),
             think_time: Duration::from_millis(options.think_time),
+            budget: options.budget,
             move_time: Duration::from_millis(options.move_time),
+            rotation_system,
+            spin_rule,
+            rotate_180,
+            replay,
+            replay_path: options.replay
         })
     }
 }
@@ -142,16 +187,16 @@ impl event::EventHandler for MainState {
                         let piece = self.board.hold
                             .replace(self.piece.kind)
                             .unwrap_or_else(|| {
-                                let piece = self.queue.next(&mut rand::thread_rng());
+                                let piece = self.queue.next(&mut self.rng);
                                 self.bot.add_piece(*self.queue.get_queue().back().unwrap());
                                 piece
                             });
-                        self.piece = Piece::spawn(&self.board, piece);
+                        self.piece = Piece::spawn(&self.board, piece, self.rotation_system, self.spin_rule);
                     }
-                    self.state = State::Moving(mv.path, Instant::now());
+                    self.state = State::Moving(mv.path, mv.uses_hold, Instant::now());
                 }
             }
-            State::Moving(ref mut path, ref mut instant) => {
+            State::Moving(ref mut path, uses_hold, ref mut instant) => {
                 if instant.elapsed() > self.move_time {
                     if let Some(mv) = path.pop_front() {
                         match mv {
@@ -159,15 +204,18 @@ impl event::EventHandler for MainState {
                             PathfinderMove::Right => self.piece.move_right(&self.board),
                             PathfinderMove::RotLeft => self.piece.turn_left(&self.board),
                             PathfinderMove::RotRight => self.piece.turn_right(&self.board),
+                            PathfinderMove::Rot180 => self.piece.turn_180(&self.board, self.rotate_180.unwrap()),
                             PathfinderMove::SonicDrop => self.piece.sonic_drop(&self.board)
                         };
                         *instant = Instant::now();
                     } else {
                         self.piece.sonic_drop(&self.board);
-                        self.board.lock_piece(self.piece);
-                        self.piece = Piece::spawn(&self.board, self.queue.next(&mut rand::thread_rng()));
+                        let lock = self.board.lock_piece(self.piece);
+                        let attack = self.replay.attack.attack(&lock);
+                        self.replay.record(self.piece, uses_hold, lock, attack, Vec::new());
+                        self.piece = Piece::spawn(&self.board, self.queue.next(&mut self.rng), self.rotation_system, self.spin_rule);
                         self.bot.add_piece(*self.queue.get_queue().back().unwrap());
-                        self.bot.begin_thinking();
+                        self.bot.begin_thinking_with(self.budget.clone());
                         self.state = State::Thinking(Instant::now());
                     }
                 }
@@ -176,6 +224,19 @@ impl event::EventHandler for MainState {
         Ok(())
     }
 
+    fn quit_event(&mut self, _ctx: &mut ggez::Context) -> bool {
+        if let Some(path) = &self.replay_path {
+            self.replay.finish(&self.board.compress());
+            match File::create(path) {
+                Ok(file) => if let Err(err) = Replay::Bot(Box::new(self.replay.clone())).write(BufWriter::new(file)) {
+                    println!("Error writing replay: {}", err);
+                },
+                Err(err) => println!("Error creating replay file: {}", err)
+            }
+        }
+        false
+    }
+
     fn resize_event(&mut self, ctx: &mut ggez::Context, width: f32, height: f32) {
         let rect = graphics::Rect::new(0.0, 0.0, width, height);
         graphics::set_screen_coordinates(ctx, rect).unwrap();
@@ -184,50 +245,13 @@ impl event::EventHandler for MainState {
     fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
         graphics::clear(ctx, graphics::BLACK);
         let mut mesh = graphics::MeshBuilder::new();
-
-        for y in 0..20 {
-            for x in 0..10 {
-                let cell = self.board.rows()[y as usize].cell_type(x as usize);
-                self.draw_cell(ctx, &mut mesh, cell, false, HOLD_WIDTH + HOLD_PADDING + x, y)?;
-            }
-        }
-        let mut piece = self.piece;
-        piece.x += HOLD_WIDTH + HOLD_PADDING;
-        self.draw_piece(ctx, &mut mesh, piece, false)?;
-        
-        let mut ghost = self.piece;
-        ghost.sonic_drop(&self.board);
-        ghost.x += HOLD_WIDTH + HOLD_PADDING;
-        self.draw_piece(ctx, &mut mesh, ghost, true)?;
-
-        if let Some(piece) = self.board.hold {
-            let piece = Piece {
-                kind: piece,
-                x: 1,
-                y: 17,
-                r: 0,
-                tspin: TspinType::None
-            };
-            self.draw_piece(ctx, &mut mesh, piece, false)?;
-        }
-
-        let queue = self.queue
-            .get_queue()
-            .clone()
-            .into_iter()
-            .rev()
-            .enumerate()
-            .rev();
-        for (i, piece) in queue {
-            let piece = Piece {
-                kind: piece,
-                x: HOLD_WIDTH + HOLD_PADDING + BOARD_WIDTH + QUEUE_PADDING + 1,
-                y: i as i32 * 4 + 1,
-                r: 0,
-                tspin: TspinType::None
-            };
-            self.draw_piece(ctx, &mut mesh, piece, false)?;
-        }
+        let queue: Vec<_> = self.queue.get_queue().iter().copied().collect();
+        let scene = Scene {
+            width: WIDTH,
+            height: HEIGHT,
+            rotation_system: self.rotation_system
+        };
+        draw_game(ctx, &mut mesh, scene, 0, &self.board, Some(self.piece), &queue)?;
 
         let mesh = mesh.build(ctx)?;
         graphics::draw(ctx, &mesh, (na::Point2::new(0.0, 0.0),))?;
@@ -236,55 +260,6 @@ impl event::EventHandler for MainState {
     }
 }
 
-impl MainState {
-    fn draw_piece(&mut self, ctx: &mut ggez::Context, mesh: &mut graphics::MeshBuilder, piece: Piece, ghost: bool) -> ggez::GameResult {
-        for &(x, y) in &piece.cells() {
-            self.draw_cell(ctx, mesh, piece.kind.cell(), ghost, x, y)?;
-        }
-        Ok(())
-    }
-
-    fn draw_cell(&mut self, ctx: &mut ggez::Context, mesh: &mut graphics::MeshBuilder, cell: CellType, ghost: bool, x: i32, y: i32) -> ggez::GameResult {
-        let (width, height) = graphics::drawable_size(ctx);
-        let cell_size = (width / WIDTH as f32).min(height / HEIGHT as f32);
-        let start_x = (width - cell_size * WIDTH as f32) / 2.0;
-        let start_y = (height - cell_size * HEIGHT as f32) / 2.0;
-        let bounds = graphics::Rect::new(
-            start_x + x as f32 * cell_size,
-            start_y + (19 - y) as f32 * cell_size,
-            cell_size,
-            cell_size
-        );
-        let color = match cell {
-            CellType::Empty => (112, 128, 144),//(112, 128, 144),
-            CellType::Garbage => (112, 128, 144),
-            CellType::Solid => (105, 105, 105),
-            CellType::J => (0, 0, 255),
-            CellType::L => (255, 69, 0),
-            CellType::S => (0, 255, 0),
-            CellType::T => (138, 43, 226),
-            CellType::Z => (255, 0, 0),
-            CellType::I => (0, 255, 255),
-            CellType::O => (255, 215, 0)
-        };
-        let mut color: graphics::Color = color.into();
-        if ghost {
-            color.a = 0.5;
-        }
-        let mode = graphics::DrawMode::fill();
-        mesh.rectangle(mode, bounds, color);
-        if cell == CellType::Empty {
-            const BORDER: f32 = 0.1;
-            let mut bounds = bounds.clone();
-            let border = cell_size * BORDER;
-            bounds.translate([border / 2.0, border / 2.0]);
-            bounds.scale(1.0 - BORDER, 1.0 - BORDER);
-            mesh.rectangle(mode, bounds, graphics::BLACK);
-        }
-        Ok(())
-    }
-}
-
 pub fn main() -> ggez::GameResult { 
     let context_builder = ggez::ContextBuilder::new("Minobot GUI", "KSean222")
         .window_setup(ggez::conf::WindowSetup {
@@ -296,6 +271,30 @@ pub fn main() -> ggez::GameResult {
             ..Default::default()
         });
     let (mut ctx, mut events_loop) = context_builder.build()?;
+    // Passing a replay file views it instead of playing a new game.
+    if let Some(path) = std::env::args().nth(1) {
+        let replay = match File::open(&path).map(BufReader::new) {
+            Ok(file) => Replay::read(file),
+            Err(err) => {
+                println!("Error opening replay: {}", err);
+                return Ok(());
+            }
+        };
+        let replay = match replay {
+            Ok(replay) => replay,
+            Err(err) => {
+                println!("Error reading replay: {}", err);
+                return Ok(());
+            }
+        };
+        if let Err(err) = replay.verify() {
+            println!("The replay doesn't play back as recorded: {}", err);
+        }
+        println!("Left/right: step a move, comma/period: step a frame, space: play/pause,");
+        println!("up/down: change speed, home/end: jump to the start/end, number and enter: jump to a move");
+        let mut viewer = Viewer::new(&replay);
+        return event::run(&mut ctx, &mut events_loop, &mut viewer);
+    }
     let mut state = MainState::new()?;
     event::run(&mut ctx, &mut events_loop, &mut state)
 }
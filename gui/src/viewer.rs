@@ -0,0 +1,222 @@
+use ggez::event;
+use ggez::graphics;
+use ggez::timer;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use ggez::nalgebra as na;
+use minotetris::*;
+use battle::*;
+
+use crate::draw::*;
+
+/// Rows of text under the boards.
+const TEXT_ROWS: i32 = 8;
+/// Pieces shown per second when playing back a bot game at normal speed.
+const PIECES_PER_SECOND: f32 = 2.0;
+const FRAMES_PER_SECOND: f32 = 60.0;
+
+/// Steps through a replay with the keyboard.
+pub struct Viewer {
+    timeline: Timeline,
+    /// The step each event happened at and its description, for each player.
+    events: Vec<Vec<(usize, String)>>,
+    /// Steps played per second at normal speed.
+    rate: f32,
+    playing: bool,
+    speed: f32,
+    progress: f32,
+    /// Digits typed so far of a move to jump to.
+    jump: String
+}
+
+impl Viewer {
+    pub fn new(replay: &Replay) -> Self {
+        let timeline = Timeline::new(replay);
+        if let Some(err) = &timeline.error {
+            println!("Stopping the replay early: {}", err);
+        }
+        let events = (0..timeline.players())
+            .map(|player| {
+                timeline.events(player)
+                    .iter()
+                    .filter_map(|(step, event)| describe(event).map(|text| (*step, text)))
+                    .collect()
+            })
+            .collect();
+        Self {
+            timeline,
+            events,
+            rate: match replay {
+                Replay::Battle(_) => FRAMES_PER_SECOND,
+                Replay::Bot(_) => PIECES_PER_SECOND
+            },
+            playing: false,
+            speed: 1.0,
+            progress: 0.0,
+            jump: String::new()
+        }
+    }
+
+    fn scene(&self) -> Scene {
+        let players = self.timeline.players() as i32;
+        Scene {
+            width: WIDTH * players + players - 1,
+            height: HEIGHT + TEXT_ROWS,
+            rotation_system: self.timeline.rotation_system
+        }
+    }
+}
+
+fn describe(event: &TetrisGameEvent) -> Option<String> {
+    Some(match event {
+        TetrisGameEvent::PieceLocked(lock) => {
+            let mut text = format!("{} locked", lock.piece.name());
+            match lock.spin {
+                TspinType::None => {}
+                TspinType::Mini => text += ", mini spin",
+                TspinType::Full => text += ", spin"
+            }
+            if lock.lines_cleared > 0 {
+                let lines = if lock.lines_cleared == 1 { "line" } else { "lines" };
+                text += &format!(", {} {}, combo {}", lock.lines_cleared, lines, lock.combo);
+            }
+            if lock.b2b_bonus {
+                text += ", b2b";
+            }
+            if lock.perfect_clear {
+                text += ", perfect clear";
+            }
+            text
+        }
+        TetrisGameEvent::PieceAutoLocked | TetrisGameEvent::PieceSpawned { .. } => return None,
+        TetrisGameEvent::Attack(lines) => format!("{} attack", lines),
+        TetrisGameEvent::GarbageSent(lines) => format!("{} garbage sent", lines),
+        TetrisGameEvent::GarbageAdded(lines) => format!("{} garbage received", lines),
+        TetrisGameEvent::GameOver => "game over".to_owned()
+    })
+}
+
+impl event::EventHandler for Viewer {
+    fn update(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
+        if self.playing {
+            self.progress += timer::delta(ctx).as_secs_f32() * self.rate * self.speed;
+            while self.progress >= 1.0 {
+                self.progress -= 1.0;
+                if !self.timeline.step_forward() {
+                    self.playing = false;
+                    self.progress = 0.0;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut ggez::Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+        let digit = match keycode {
+            KeyCode::Key0 | KeyCode::Numpad0 => Some('0'),
+            KeyCode::Key1 | KeyCode::Numpad1 => Some('1'),
+            KeyCode::Key2 | KeyCode::Numpad2 => Some('2'),
+            KeyCode::Key3 | KeyCode::Numpad3 => Some('3'),
+            KeyCode::Key4 | KeyCode::Numpad4 => Some('4'),
+            KeyCode::Key5 | KeyCode::Numpad5 => Some('5'),
+            KeyCode::Key6 | KeyCode::Numpad6 => Some('6'),
+            KeyCode::Key7 | KeyCode::Numpad7 => Some('7'),
+            KeyCode::Key8 | KeyCode::Numpad8 => Some('8'),
+            KeyCode::Key9 | KeyCode::Numpad9 => Some('9'),
+            _ => None
+        };
+        if let Some(digit) = digit {
+            self.jump.push(digit);
+            return;
+        }
+        let seeking = matches!(
+            keycode,
+            KeyCode::Right | KeyCode::Left | KeyCode::Period | KeyCode::Comma | KeyCode::Home | KeyCode::End
+        );
+        if seeking {
+            self.progress = 0.0;
+        }
+        match keycode {
+            KeyCode::Right => self.timeline.next_move(),
+            KeyCode::Left => self.timeline.prev_move(),
+            KeyCode::Period => {
+                self.timeline.step_forward();
+            }
+            KeyCode::Comma => self.timeline.step_back(),
+            KeyCode::Home => self.timeline.seek(0),
+            KeyCode::End => self.timeline.seek(self.timeline.last_step()),
+            KeyCode::Space => {
+                if !self.playing && self.timeline.position() == self.timeline.last_step() {
+                    self.timeline.seek(0);
+                    self.progress = 0.0;
+                }
+                self.playing = !self.playing;
+            }
+            KeyCode::Up => self.speed = (self.speed * 2.0).min(64.0),
+            KeyCode::Down => self.speed = (self.speed / 2.0).max(1.0 / 16.0),
+            KeyCode::Back => {
+                self.jump.pop();
+            }
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                if let Ok(mv) = self.jump.parse::<usize>() {
+                    self.timeline.seek_move(mv);
+                    self.progress = 0.0;
+                }
+                self.jump.clear();
+            }
+            KeyCode::Escape => event::quit(ctx),
+            _ => {}
+        }
+    }
+
+    fn resize_event(&mut self, ctx: &mut ggez::Context, width: f32, height: f32) {
+        let rect = graphics::Rect::new(0.0, 0.0, width, height);
+        graphics::set_screen_coordinates(ctx, rect).unwrap();
+    }
+
+    fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
+        graphics::clear(ctx, graphics::BLACK);
+        let scene = self.scene();
+        let mut mesh = graphics::MeshBuilder::new();
+        for (i, player) in self.timeline.views().iter().enumerate() {
+            let x = i as i32 * (WIDTH + 1);
+            draw_game(ctx, &mut mesh, scene, x, &player.board, player.piece, &player.queue)?;
+        }
+        let mesh = mesh.build(ctx)?;
+        graphics::draw(ctx, &mesh, (na::Point2::new(0.0, 0.0),))?;
+
+        let mut status = format!(
+            "Move {}/{}  step {}/{}  {}x{}",
+            self.timeline.current_move(),
+            self.timeline.last_move(),
+            self.timeline.position(),
+            self.timeline.last_step(),
+            self.speed,
+            if self.playing { "" } else { "  paused" }
+        );
+        if !self.jump.is_empty() {
+            status += &format!("  go to move {}", self.jump);
+        }
+        scene.draw_text(ctx, &status, 0, HEIGHT)?;
+        for (i, player) in self.timeline.views().iter().enumerate() {
+            let x = i as i32 * (WIDTH + 1);
+            let mut row = HEIGHT + 1;
+            if player.garbage_pending > 0 {
+                scene.draw_text(ctx, &format!("{} garbage pending", player.garbage_pending), x, row)?;
+                row += 1;
+            }
+            let events: Vec<_> = self.events[i]
+                .iter()
+                .filter(|&&(step, _)| step <= self.timeline.position())
+                .collect();
+            let shown = (HEIGHT + TEXT_ROWS - row) as usize;
+            for (step, text) in events.iter().skip(events.len().saturating_sub(shown)) {
+                let marker = if *step == self.timeline.position() { "> " } else { "  " };
+                scene.draw_text(ctx, &format!("{}{}", marker, text), x, row)?;
+                row += 1;
+            }
+        }
+        graphics::present(ctx)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::ops::{Index, IndexMut};
+
+use crate::bot::Node;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(u32);
+
+/// Pool of search tree nodes. Freed slots are recycled by later allocations,
+/// so discarding a subtree never hands memory back to the allocator.
+#[derive(Default)]
+pub struct NodeArena {
+    nodes: Vec<Node>,
+    free: Vec<NodeId>
+}
+
+impl NodeArena {
+    pub fn alloc(&mut self, mut node: Node) -> NodeId {
+        if let Some(id) = self.free.pop() {
+            let slot = &mut self.nodes[id.0 as usize];
+            let mut children = std::mem::take(&mut slot.children);
+            children.clear();
+            node.children = children;
+            *slot = node;
+            id
+        } else {
+            self.nodes.push(node);
+            NodeId(self.nodes.len() as u32 - 1)
+        }
+    }
+    /// Frees a node along with its whole subtree.
+    pub fn free(&mut self, id: NodeId) {
+        let mut i = self.free.len();
+        self.free.push(id);
+        while i < self.free.len() {
+            let node = &self.nodes[self.free[i].0 as usize];
+            self.free.extend_from_slice(&node.children);
+            i += 1;
+        }
+    }
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+    }
+    /// Number of nodes in use.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Index<NodeId> for NodeArena {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl IndexMut<NodeId> for NodeArena {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0 as usize]
+    }
+}
@@ -1,62 +1,266 @@
+use std::sync::Arc;
+use std::cmp::Reverse;
+use std::time::{Instant, Duration};
+
 use serde::{ Serialize, Deserialize };
+use enumset::EnumSet;
 
 use crate::pathfinder::Moves;
 use crate::evaluator::{ Evaluator, StandardEvaluator };
+use crate::transposition::TranspositionTable;
+use crate::arena::{ NodeArena, NodeId };
 use minotetris::*;
 
 pub struct Bot<E=StandardEvaluator> {
     pub data: BotData<E>,
-    pub root: Node,
+    pub nodes: NodeArena,
+    pub root: NodeId,
 }
 
 pub struct BotData<E> {
     pub queue: Vec<PieceType>,
     pub settings: BotSettings,
-    evaluator: E,
+    pub transpositions: TranspositionTable,
+    pub pieces_used: u32,
+    /// Pieces left in the current 7-bag after the last piece in `queue`,
+    /// or `None` if it isn't known. Speculation needs it.
+    pub bag: Option<EnumSet<PieceType>>,
+    /// Incoming garbage, in the order it will arrive.
+    pub garbage: Vec<GarbageBatch>,
+    evaluator: Arc<E>,
+}
+
+/// Garbage lines sent by the opponent that haven't been inserted yet.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct GarbageBatch {
+    pub lines: u32,
+    /// Column of the hole, if it is known in advance.
+    pub hole: Option<i32>,
+    /// Number of pieces that can be placed before these lines arrive.
+    pub delay: u32
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct BotSettings {
     pub use_hold: bool,
-    pub exploration_exploitation_constant: f32
+    pub exploration_exploitation_constant: f32,
+    pub threads: u32,
+    /// Keep searching past the end of the queue by guessing the next pieces from the 7-bag.
+    pub speculate: bool,
+    /// Prune the search tree and the transposition table once either holds this many nodes.
+    pub max_nodes: Option<u32>,
+    pub rotation_system: RotationSystem,
+    pub spin_rule: SpinRule,
+    /// Kick table for 180 rotations, or `None` if the game doesn't have them.
+    pub rotate_180: Option<Kicks180>,
+    /// Attack table of the game being played, used to work out how much garbage moves send.
+    pub attack: AttackRules
 }
 
 impl Default for BotSettings {
     fn default() -> Self {
         BotSettings {
             use_hold: true,
-            exploration_exploitation_constant: std::f32::consts::SQRT_2
+            exploration_exploitation_constant: std::f32::consts::SQRT_2,
+            threads: 1,
+            speculate: false,
+            max_nodes: None,
+            rotation_system: RotationSystem::Srs,
+            spin_rule: SpinRule::TSpin,
+            rotate_180: None,
+            attack: AttackRules::default()
         }
     }
 }
 
+/// Limits on how much thinking goes into each move. Thinking stops at whichever limit is hit first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ThinkBudget {
+    /// Number of `think` iterations.
+    pub thinks: Option<u32>,
+    /// Number of nodes added to the tree.
+    pub nodes: Option<u32>,
+    /// Time cap in milliseconds.
+    pub time: Option<u64>
+}
+
+impl ThinkBudget {
+    pub fn exhausted(&self, thinks: u32, nodes: u32, elapsed: Duration) -> bool {
+        self.thinks.is_some_and(|limit| thinks >= limit)
+            || self.nodes.is_some_and(|limit| nodes >= limit)
+            || self.time.is_some_and(|limit| elapsed >= Duration::from_millis(limit))
+    }
+    /// Whether the budget ever runs out.
+    pub fn is_limited(&self) -> bool {
+        self.is_counted() || self.time.is_some()
+    }
+    /// Whether there is a limit that doesn't depend on how fast the machine is.
+    pub fn is_counted(&self) -> bool {
+        self.thinks.is_some() || self.nodes.is_some()
+    }
+}
+
+/// Fraction of `max_nodes` the tree is pruned back down to once it is full.
+const PRUNE_TARGET: f32 = 0.75;
+
+/// A subtree that can be pruned, found by `Bot::prunable`.
+struct PruneCandidate {
+    node: NodeId,
+    /// How far behind its best sibling this node is.
+    regret: i64,
+    size: usize,
+    /// Whether an expansion is in flight somewhere in this subtree.
+    busy: bool,
+    /// Index of the closest candidate above this one.
+    parent: Option<usize>
+}
+
 impl<E: Evaluator> Bot<E> {
     pub fn new(board: Board, evaluator: E, settings: BotSettings) -> Self {
+        let mut nodes = NodeArena::default();
+        let root = nodes.alloc(Node::root(board, 0));
         Bot {
             data: BotData {
                 queue: Vec::new(),
-                evaluator,
+                transpositions: TranspositionTable::default(),
+                pieces_used: 0,
+                bag: None,
+                garbage: Vec::new(),
+                evaluator: Arc::new(evaluator),
                 settings
             },
-            root: Node::root(board),
+            nodes,
+            root
         }
     }
+    pub fn root(&self) -> &Node {
+        &self.nodes[self.root]
+    }
     pub fn update_queue(&mut self, mino: PieceType) {
+        let depth = self.data.queue.len() as u32;
         self.data.queue.push(mino);
+        self.data.bag = self.data.bag.and_then(|bag| draw_from_bag(bag, mino));
+        self.reveal(self.root, depth, mino);
+    }
+    /// Sets the pieces left in the current 7-bag after the last piece in the queue,
+    /// which lets the bot speculate past the end of the queue.
+    pub fn set_bag(&mut self, bag: EnumSet<PieceType>) {
+        if self.data.bag != Some(bag) {
+            self.data.bag = Some(bag);
+            let board = self.root().board.clone();
+            self.reset(board);
+        }
+    }
+    /// Queues incoming garbage. The tree was searched without it, so this restarts the search.
+    pub fn add_garbage(&mut self, batch: GarbageBatch) {
+        self.data.garbage.push(batch);
+        let board = self.root().board.clone();
+        self.reset(board);
+    }
+    /// Forgets all incoming garbage, for when it has been inserted into the board passed to `reset`.
+    pub fn clear_garbage(&mut self) {
+        if !self.data.garbage.is_empty() {
+            self.data.garbage.clear();
+            let board = self.root().board.clone();
+            self.reset(board);
+        }
     }
     pub fn reset(&mut self, board: Board) {
-        self.root = Node::root(board);
+        self.nodes.clear();
+        self.root = self.nodes.alloc(Node::root(board, self.data.pieces_used));
+        self.data.transpositions.clear();
     }
     pub fn think(&mut self) -> bool {
-        self.root.update(&mut self.data);
-        self.root.finished
+        if let Some(expansion) = self.select() {
+            let children = expansion.expand(&*self.data.evaluator);
+            self.backup(expansion, children);
+        }
+        self.root().finished
+    }
+    /// Thinks until `budget` runs out or there is nothing left to expand.
+    /// Returns the number of thinks and the number of nodes added.
+    pub fn think_for(&mut self, budget: &ThinkBudget) -> (u32, u32) {
+        let start = Instant::now();
+        let mut thinks = 0;
+        let mut nodes = 0;
+        while !budget.exhausted(thinks, nodes, start.elapsed()) {
+            let expansion = match self.select() {
+                Some(expansion) => expansion,
+                None => break
+            };
+            let children = expansion.expand(&*self.data.evaluator);
+            nodes += self.backup(expansion, children);
+            thinks += 1;
+        }
+        (thinks, nodes)
+    }
+    pub fn evaluator(&self) -> Arc<E> {
+        self.data.evaluator.clone()
+    }
+    /// Claims the most promising leaf for expansion, applying virtual loss along its path.
+    /// Returns `None` if the tree is finished or every selectable leaf is already claimed.
+    pub(crate) fn select(&mut self) -> Option<Expansion> {
+        let mut path = Vec::new();
+        if let Selection::Expand(leaf) = self.select_node(self.root, &mut path) {
+            let leaf = &self.nodes[leaf];
+            let bag = if leaf.depth as usize == self.data.queue.len() {
+                self.data.bag.unwrap_or_default()
+            } else {
+                leaf.bag
+            };
+            Some(Expansion {
+                path,
+                board: leaf.board.clone(),
+                depth: leaf.depth,
+                queue: self.data.queue.clone(),
+                bag: if bag.is_empty() { EnumSet::all() } else { bag },
+                garbage: self.data.garbage.clone(),
+                garbage_consumed: leaf.garbage_consumed,
+                hold_empty: self.root().board.hold.is_none(),
+                attack: self.data.settings.attack.clone(),
+                use_hold: self.data.settings.use_hold,
+                rotation_system: self.data.settings.rotation_system,
+                spin_rule: self.data.settings.spin_rule,
+                rotate_180: self.data.settings.rotate_180,
+                speculate: self.data.settings.speculate,
+                pieces_used: self.data.pieces_used
+            })
+        } else {
+            None
+        }
+    }
+    /// Returns the number of nodes added to the tree.
+    pub(crate) fn backup(&mut self, expansion: Expansion, mut children: Vec<Node>) -> u32 {
+        if let Some(max_nodes) = self.data.settings.max_nodes {
+            let max_nodes = max_nodes as usize;
+            if self.nodes.len() + children.len() > max_nodes {
+                self.prune((max_nodes as f32 * PRUNE_TARGET) as usize);
+            }
+            if self.nodes.len() + children.len() > max_nodes {
+                // Nothing left to prune, so this leaf has to stay a leaf.
+                children.clear();
+            }
+            // The table outlives the nodes it was filled from, so it needs its own cap.
+            if self.data.transpositions.len() + children.len() > max_nodes {
+                self.data.transpositions.shrink_to((max_nodes as f32 * PRUNE_TARGET) as usize);
+            }
+        }
+        let added = children.len() as u32;
+        self.backup_node(self.root, &expansion.path, children);
+        added
     }
     pub fn next_move(&mut self) -> Option<&Node> {
-        let root = self.root.children
-            .drain(..)
-            .max_by_key(|c| c.total_value());
+        if self.is_chance(self.root) {
+            return None;
+        }
+        let root = self.root().children
+            .iter()
+            .copied()
+            .max_by_key(|&c| self.nodes[c].total_value());
         if let Some(root) = root {
-            let pieces_used = if root.uses_hold && self.root.board.hold.is_none() {
+            let pieces_used = if self.nodes[root].uses_hold && self.root().board.hold.is_none() {
                 2
             } else {
                 1
@@ -64,25 +268,316 @@ impl<E: Evaluator> Bot<E> {
             for _ in 0..pieces_used {
                 self.data.queue.remove(0);
             }
+            self.data.pieces_used += pieces_used;
+            self.data.transpositions.retain_from(self.data.pieces_used);
+            let mut consumed = self.nodes[root].garbage_consumed;
+            self.data.garbage.retain_mut(|batch| {
+                let lines = consumed.min(batch.lines);
+                batch.lines -= lines;
+                batch.delay = batch.delay.saturating_sub(1);
+                consumed -= lines;
+                batch.lines > 0
+            });
+            let siblings = std::mem::take(&mut self.nodes[self.root].children);
+            for sibling in siblings {
+                if sibling != root {
+                    self.nodes.free(sibling);
+                }
+            }
+            self.nodes.free(self.root);
             self.root = root;
-            self.root.advance(pieces_used);
-            // for &row in self.root.board.rows().iter().rev().skip(20) {
-            //     for x in 0..10 {
-            //         print!("{}", if row.get(x) {
-            //             "[]"
-            //         } else {
-            //             ".."
-            //         });
-            //     }
-            //     println!()
-            // }
-            // println!("Hold: {:?}, Queue: {:?}", self.root.board.hold, self.data.queue);
-            // println!();
-            Some(&self.root)
+            self.advance(pieces_used);
+            Some(self.root())
         } else {
             None
         }
     }
+    fn select_node(&mut self, id: NodeId, path: &mut Vec<u64>) -> Selection {
+        let node = &mut self.nodes[id];
+        if node.children.is_empty() {
+            return if node.expanding {
+                Selection::Busy
+            } else if node.finished || !node.expandable(&self.data) {
+                node.finished = true;
+                Selection::Finished
+            } else {
+                node.expanding = true;
+                node.visits += VIRTUAL_LOSS;
+                Selection::Expand(id)
+            };
+        }
+        let node = &self.nodes[id];
+        let mut scores: Vec<_> = node.children
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| {
+                let c = &self.nodes[c];
+                if c.finished {
+                    f32::NEG_INFINITY
+                } else {
+                    (i as f32) / (node.children.len() as f32) +
+                    self.data.settings.exploration_exploitation_constant *
+                    ((node.visits as f32).ln() / (c.visits as f32)).sqrt()
+                }
+            })
+            .collect();
+        let mut busy = false;
+        loop {
+            let (child_index, &score) = scores
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .unwrap();
+            if score == f32::NEG_INFINITY {
+                break;
+            }
+            let child = self.nodes[id].children[child_index];
+            path.push(self.nodes[child].hash);
+            match self.select_node(child, path) {
+                Selection::Expand(leaf) => {
+                    self.nodes[id].visits += VIRTUAL_LOSS;
+                    return Selection::Expand(leaf);
+                }
+                Selection::Busy => busy = true,
+                Selection::Finished => {}
+            }
+            path.pop();
+            scores[child_index] = f32::NEG_INFINITY;
+        }
+        if busy {
+            Selection::Busy
+        } else {
+            self.nodes[id].finished = true;
+            Selection::Finished
+        }
+    }
+    fn backup_node(&mut self, id: NodeId, path: &[u64], children: Vec<Node>) -> u32 {
+        self.nodes[id].visits -= VIRTUAL_LOSS;
+        if let Some((&hash, path)) = path.split_first() {
+            let child = self.nodes[id].children
+                .iter()
+                .copied()
+                .find(|&c| self.nodes[c].hash == hash)
+                .unwrap();
+            let visits = self.backup_node(child, path, children);
+            self.sort_children(id);
+            self.update_value(id);
+            let node = &mut self.nodes[id];
+            node.visits += visits;
+            self.data.transpositions.store(node.hash, self.data.pieces_used + node.depth, node);
+            visits
+        } else {
+            self.nodes[id].expanding = false;
+            self.attach(id, children)
+        }
+    }
+    fn attach(&mut self, id: NodeId, children: Vec<Node>) -> u32 {
+        if children.is_empty() {
+            self.nodes[id].finished = true;
+            return 0;
+        }
+        let visits = children.len() as u32;
+        for mut child in children {
+            if !child.lock.block_out {
+                if let Some(entry) = self.data.transpositions.get(child.hash) {
+                    child.value = entry.value;
+                    child.max_child_reward = entry.max_child_reward;
+                    child.visits = entry.visits;
+                }
+            }
+            let child = self.nodes.alloc(child);
+            self.nodes[id].children.push(child);
+        }
+        self.sort_children(id);
+        self.update_value(id);
+        let node = &mut self.nodes[id];
+        node.visits += visits;
+        self.data.transpositions.store(node.hash, self.data.pieces_used + node.depth, node);
+        visits
+    }
+    /// The best line of play found so far, stopping where the queue runs out.
+    pub fn plan(&self) -> Vec<&Node> {
+        let mut plan = Vec::new();
+        let mut id = self.root;
+        while !self.is_chance(id) {
+            let best = self.nodes[id].children
+                .iter()
+                .copied()
+                .max_by_key(|&c| self.nodes[c].total_value());
+            match best {
+                Some(child) => {
+                    plan.push(&self.nodes[child]);
+                    id = child;
+                }
+                None => break
+            }
+        }
+        plan
+    }
+    /// `plan` as a fumen, with a last page showing where it ends up.
+    pub fn plan_fumen(&self) -> Fumen<u16> {
+        let mut board = &self.root().board;
+        let mut pages = Vec::new();
+        for node in self.plan() {
+            let mut page = FumenPage::new(board.clone());
+            page.piece = Some(node.mv);
+            pages.push(page);
+            board = &node.board;
+        }
+        pages.push(FumenPage::new(board.clone()));
+        Fumen { pages }
+    }
+    /// Whether this node's children are guesses for a piece past the end of the queue.
+    fn is_chance(&self, id: NodeId) -> bool {
+        self.nodes[id].children
+            .first()
+            .is_some_and(|&c| self.nodes[c].speculated.is_some())
+    }
+    fn sort_children(&mut self, id: NodeId) {
+        let mut children = std::mem::take(&mut self.nodes[id].children);
+        children.sort_by_key(|&c| self.nodes[c].total_value());
+        self.nodes[id].children = children;
+    }
+    /// Takes the value of the best child, or for a chance node,
+    /// the average over each possible piece of the best child given that piece.
+    fn update_value(&mut self, id: NodeId) {
+        let children = &self.nodes[id].children;
+        let (value, max_child_reward) = if self.is_chance(id) {
+            let mut best: [Option<&Node>; 7] = [None; 7];
+            for &c in children {
+                let c = &self.nodes[c];
+                best[c.speculated.unwrap() as usize] = Some(c);
+            }
+            let best: Vec<_> = best.iter().flatten().collect();
+            let count = best.len() as i64;
+            let value = best.iter().map(|c| c.value as i64).sum::<i64>() / count;
+            let reward = best.iter()
+                .map(|c| c.reward.saturating_add(c.max_child_reward) as i64)
+                .sum::<i64>() / count;
+            (value as i32, reward as i32)
+        } else {
+            let best = &self.nodes[*children.last().unwrap()];
+            (best.value, best.reward.saturating_add(best.max_child_reward))
+        };
+        let node = &mut self.nodes[id];
+        node.value = value;
+        node.max_child_reward = max_child_reward;
+    }
+    /// Collapses the chance nodes guessing the piece at `depth` onto the revealed piece,
+    /// then refreshes the values above them. Returns whether anything changed.
+    fn reveal(&mut self, id: NodeId, depth: u32, piece: PieceType) -> bool {
+        let node = &self.nodes[id];
+        if node.depth > depth || node.children.is_empty() {
+            return false;
+        }
+        if node.depth == depth {
+            if !self.is_chance(id) {
+                return false;
+            }
+            let mut children = std::mem::take(&mut self.nodes[id].children);
+            children.retain(|&c| if self.nodes[c].speculated == Some(piece) {
+                self.nodes[c].speculated = None;
+                true
+            } else {
+                self.nodes.free(c);
+                false
+            });
+            self.nodes[id].children = children;
+            if self.nodes[id].children.is_empty() {
+                return true;
+            }
+        } else {
+            let mut changed = false;
+            for i in 0..self.nodes[id].children.len() {
+                let child = self.nodes[id].children[i];
+                changed |= self.reveal(child, depth, piece);
+            }
+            if !changed {
+                return false;
+            }
+            self.sort_children(id);
+        }
+        self.update_value(id);
+        true
+    }
+    /// Collapses the subtrees furthest behind their best sibling back into leaves
+    /// until at most `target` nodes are in use.
+    fn prune(&mut self, target: usize) {
+        let mut candidates = Vec::new();
+        self.prunable(self.root, None, &mut candidates);
+        let mut order: Vec<_> = (0..candidates.len()).collect();
+        order.sort_by_key(|&i| Reverse((candidates[i].regret, candidates[i].size)));
+        let mut pruned = vec![false; candidates.len()];
+        for i in order {
+            if self.nodes.len() <= target {
+                break;
+            }
+            let mut parent = candidates[i].parent;
+            while let Some(p) = parent {
+                if pruned[p] {
+                    break;
+                }
+                parent = candidates[p].parent;
+            }
+            if parent.is_some() || candidates[i].busy {
+                // Either already freed along with an ancestor, or still being expanded.
+                continue;
+            }
+            let node = candidates[i].node;
+            for j in 0..self.nodes[node].children.len() {
+                let child = self.nodes[node].children[j];
+                self.nodes.free(child);
+            }
+            self.nodes[node].children.clear();
+            self.nodes[node].finished = false;
+            pruned[i] = true;
+        }
+    }
+    /// Collects every internal node that isn't the best child of its parent.
+    /// Returns the size of the subtree and whether an expansion is in flight inside it.
+    fn prunable(&self, id: NodeId, parent: Option<usize>, candidates: &mut Vec<PruneCandidate>) -> (usize, bool) {
+        let node = &self.nodes[id];
+        let best = match node.children.last() {
+            Some(&best) => self.nodes[best].total_value() as i64,
+            None => return (1, node.expanding)
+        };
+        let mut size = 1;
+        let mut busy = false;
+        for (i, &c) in node.children.iter().enumerate() {
+            let child = &self.nodes[c];
+            if child.children.is_empty() || i == node.children.len() - 1 {
+                let (child_size, child_busy) = self.prunable(c, parent, candidates);
+                size += child_size;
+                busy |= child_busy;
+                continue;
+            }
+            let index = candidates.len();
+            candidates.push(PruneCandidate {
+                node: c,
+                regret: best - child.total_value() as i64,
+                size: 0,
+                busy: false,
+                parent
+            });
+            let (child_size, child_busy) = self.prunable(c, Some(index), candidates);
+            size += child_size;
+            busy |= child_busy;
+            candidates[index].size = child_size;
+            candidates[index].busy = child_busy;
+        }
+        (size, busy)
+    }
+    fn advance(&mut self, pieces_used: u32) {
+        let garbage_consumed = self.root().garbage_consumed;
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id];
+            node.finished = false;
+            node.depth -= pieces_used;
+            node.garbage_consumed -= garbage_consumed;
+            stack.extend_from_slice(&node.children);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -92,22 +587,87 @@ pub struct Node {
     pub move_dist: i32,
     pub lock: LockResult,
     pub uses_hold: bool,
+    pub hash: u64,
+    /// The piece this node assumed would come next, if it was past the end of the queue.
+    pub speculated: Option<PieceType>,
+    /// Pieces left in the bag, for nodes further than one piece past the end of the queue.
+    pub bag: EnumSet<PieceType>,
+    /// Lines this move would send before cancelling.
+    pub attack: u32,
+    /// Incoming lines cancelled by this move's attack.
+    pub garbage_cancelled: u32,
+    /// Incoming lines inserted into the board after this move.
+    pub garbage_received: u32,
+    /// Incoming lines still waiting after this move.
+    pub garbage_pending: u32,
+    /// Lines of `BotData::garbage` cancelled or received on the way to this node.
+    pub garbage_consumed: u32,
 
-    pub children: Vec<Node>,
+    pub children: Vec<NodeId>,
     pub value: i32,
     pub reward: i32,
     pub max_child_reward: i32,
     pub visits: u32,
     pub finished: bool,
+    pub expanding: bool,
     pub depth: u32
 }
 
+const VIRTUAL_LOSS: u32 = 1;
+
+/// Takes `piece` out of a 7-bag holding `bag`, starting a new bag if it is empty.
+/// Returns `None` if `piece` can't have come out of it.
+pub fn draw_from_bag(mut bag: EnumSet<PieceType>, piece: PieceType) -> Option<EnumSet<PieceType>> {
+    if bag.is_empty() {
+        bag = EnumSet::all();
+    }
+    if bag.remove(piece) {
+        Some(bag)
+    } else {
+        None
+    }
+}
+
+enum Selection {
+    Expand(NodeId),
+    Busy,
+    Finished
+}
+
+/// A leaf claimed by `Bot::select`, carrying everything needed to expand it without the tree.
+pub(crate) struct Expansion {
+    path: Vec<u64>,
+    board: Board,
+    depth: u32,
+    queue: Vec<PieceType>,
+    bag: EnumSet<PieceType>,
+    garbage: Vec<GarbageBatch>,
+    garbage_consumed: u32,
+    /// Whether the root's hold is empty, in which case the first hold uses up a piece without placing it.
+    hold_empty: bool,
+    attack: AttackRules,
+    use_hold: bool,
+    rotation_system: RotationSystem,
+    spin_rule: SpinRule,
+    rotate_180: Option<Kicks180>,
+    speculate: bool,
+    pieces_used: u32
+}
+
 impl Node {
     fn total_value(&self) -> i32 {
         self.value.saturating_add(self.reward).saturating_add(self.max_child_reward)
     }
-    fn root(board: Board) -> Self {
+    fn root(board: Board, pieces_used: u32) -> Self {
         Self {
+            hash: TranspositionTable::key(&board, pieces_used),
+            speculated: None,
+            bag: EnumSet::new(),
+            attack: 0,
+            garbage_cancelled: 0,
+            garbage_received: 0,
+            garbage_pending: 0,
+            garbage_consumed: 0,
             board,
             children: Vec::new(),
             mv: Piece {
@@ -115,105 +675,145 @@ impl Node {
                 x: 0,
                 y: 0,
                 r: 0,
-                tspin: TspinType::None
+                tspin: TspinType::None,
+                system: RotationSystem::Srs,
+                spin_rule: SpinRule::TSpin
             },
             move_dist: 0,
             lock: LockResult {
+                piece: PieceType::O,
+                spin: TspinType::None,
                 lines_cleared: 0,
                 block_out: false,
                 combo: 0,
-                b2b_bonus: false
+                b2b_bonus: false,
+                perfect_clear: false
             },
-            value: std::i32::MIN,
+            value: i32::MIN,
             reward: 0,
             max_child_reward: 0,
             visits: 1,
             uses_hold: false,
             finished: false,
+            expanding: false,
             depth: 0
         }
     }
-    fn update<E: Evaluator>(&mut self, data: &BotData<E>) -> ((i32, i32), u32) {
-        let mut child_index = None;
-        let mut score = std::f32::NEG_INFINITY;
-        for (i, c) in self.children.iter().enumerate() {
-            if !c.finished {
-                let child_score =
-                    (i as f32) / (self.children.len() as f32) +
-                    data.settings.exploration_exploitation_constant * 
-                    ((self.visits as f32).ln() / (c.visits as f32)).sqrt();
-                if child_score > score {
-                    child_index = Some(i);
-                    score = child_score;
+    fn expandable<E>(&self, data: &BotData<E>) -> bool {
+        (self.depth as usize) < data.queue.len() ||
+            data.settings.speculate && self.depth > 0 && data.bag.is_some()
+    }
+}
+
+impl Expansion {
+    pub(crate) fn expand<E: Evaluator>(&self, evaluator: &E) -> Vec<Node> {
+        let mut children = Vec::new();
+        if let Some(&current) = self.queue.get(self.depth as usize) {
+            let piece = Piece::spawn(&self.board, current, self.rotation_system, self.spin_rule);
+            for mv in Moves::moves(&self.board, piece, self.rotate_180).moves {
+                self.create_child(evaluator, &mut children, mv, false, current, None);
+            }
+            if self.use_hold {
+                let mut hold_board = self.board.clone();
+                let piece_type = hold_board.hold
+                    .replace(current)
+                    .or(self.queue.get((self.depth + 1) as usize).copied());
+                if let Some(piece_type) = piece_type {
+                    let piece = Piece::spawn(&self.board, piece_type, self.rotation_system, self.spin_rule);
+                    for mv in Moves::moves(&hold_board, piece, self.rotate_180).moves {
+                        self.create_child(evaluator, &mut children, mv, true, current, None);
+                    }
                 }
             }
-        }
-        if let Some(child_index) = child_index {
-            let ((value, reward), visits) = self.children[child_index].update(data);
-            let child = self.children.remove(child_index);
-            let child_index = self.children
-                .iter()
-                .position(|c| c.total_value() > child.total_value())
-                .unwrap_or(self.children.len());
-            self.children.insert(child_index, child);
-            if value.saturating_add(reward) > self.value.saturating_add(self.max_child_reward) {
-                self.value = value;
-                self.max_child_reward = reward;
-            }
-            self.visits += visits;
-            ((value, self.reward.saturating_add(reward)), visits)
-        } else if self.children.is_empty() {
-            self.expand(data)
         } else {
-            self.finished = true;
-            ((std::i32::MIN, 0), 0)
-        }
-    }
-    fn expand<E: Evaluator>(&mut self, data: &BotData<E>) -> ((i32, i32), u32) {
-        if self.depth as usize >= data.queue.len() {
-            self.finished = true;
-            return ((std::i32::MIN, 0), 0);
-        }
-        let piece = Piece::spawn(&self.board, data.queue[self.depth as usize]);
-        for mv in Moves::moves(&self.board, piece).moves {
-            self.create_child(data, mv, false);
-        }
-        if data.settings.use_hold {
-            let mut hold_board = self.board.clone();
-            let piece_type = hold_board.hold
-                .replace(data.queue[self.depth as usize])
-                .or(data.queue.get((self.depth + 1) as usize).copied());
-            if let Some(piece_type) = piece_type {
-                let piece = Piece::spawn(&self.board, piece_type);
-                for mv in Moves::moves(&hold_board, piece).moves {
-                    self.create_child(data, mv, true);
+            // Past the end of the queue, so try every piece that could still come out of the bag.
+            // Holding into an empty hold would need a second unknown piece, so it isn't considered.
+            let hold_moves = match self.board.hold {
+                Some(hold) if self.use_hold => Moves::moves(&self.board, Piece::spawn(&self.board, hold, self.rotation_system, self.spin_rule), self.rotate_180).moves,
+                _ => Vec::new()
+            };
+            for current in self.bag {
+                let piece = Piece::spawn(&self.board, current, self.rotation_system, self.spin_rule);
+                for mv in Moves::moves(&self.board, piece, self.rotate_180).moves {
+                    self.create_child(evaluator, &mut children, mv, false, current, Some(current));
+                }
+                for &mv in &hold_moves {
+                    self.create_child(evaluator, &mut children, mv, true, current, Some(current));
                 }
             }
         }
-        if self.children.is_empty() {
-            self.finished = true;
-            ((std::i32::MIN, 0), 0)
-        } else {
-            self.children.sort_unstable_by_key(|c| c.total_value());
-            let best = self.children.last().unwrap();
-            let visits = self.children.len() as u32;
-            self.value = best.value;
-            self.max_child_reward = best.reward;
-            self.visits += visits;
-            ((best.value, self.reward.saturating_add(best.reward)), visits)
-        }
+        children
     }
-    fn create_child<E: Evaluator>(&mut self, data: &BotData<E>, (mv, move_dist): (Piece, i32), uses_hold: bool) {
+    fn create_child<E: Evaluator>(
+        &self,
+        evaluator: &E,
+        children: &mut Vec<Node>,
+        (mv, move_dist): (Piece, i32),
+        uses_hold: bool,
+        current: PieceType,
+        speculated: Option<PieceType>
+    ) {
         let mut board = self.board.clone();
         let mut child_depth = self.depth;
         if uses_hold {
-            if board.hold.replace(data.queue[child_depth as usize]).is_none() {
+            if board.hold.replace(current).is_none() {
                 child_depth += 1;
             }
         }
-        let lock = board.lock_piece(mv);
+        let mut lock = board.lock_piece(mv);
         child_depth += 1;
+
+        let attack = self.attack.attack(&lock);
+        let incoming = self.garbage.iter().map(|b| b.lines).sum::<u32>();
+        let garbage_cancelled = attack.min(incoming - self.garbage_consumed);
+        let mut garbage_consumed = self.garbage_consumed + garbage_cancelled;
+        let placements = child_depth - (self.hold_empty && board.hold.is_some()) as u32;
+        let mut holes = Vec::new();
+        let mut start = 0;
+        for batch in &self.garbage {
+            if batch.delay >= placements {
+                break;
+            }
+            let end = start + batch.lines;
+            if end > garbage_consumed {
+                // Unknown holes are assumed to line up with the lowest column.
+                let hole = batch.hole.unwrap_or_else(|| board
+                    .column_heights()
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|&(_, h)| h)
+                    .unwrap()
+                    .0 as i32
+                );
+                let lines = end - garbage_consumed.max(start);
+                holes.resize(holes.len() + lines as usize, hole);
+                garbage_consumed = end;
+            }
+            start = end;
+        }
+        if !holes.is_empty() && board.add_garbage(&holes) {
+            lock.block_out = true;
+        }
+
+        let mut bag = self.bag;
+        let mut hash = if let Some(piece) = speculated {
+            bag.remove(piece);
+            TranspositionTable::speculative_key(&board, self.pieces_used + child_depth, bag)
+        } else {
+            TranspositionTable::key(&board, self.pieces_used + child_depth)
+        };
+        if garbage_consumed > 0 {
+            hash ^= TranspositionTable::garbage_key(garbage_consumed);
+        }
         let mut child = Node {
+            hash,
+            speculated,
+            bag,
+            attack,
+            garbage_cancelled,
+            garbage_received: holes.len() as u32,
+            garbage_pending: incoming - garbage_consumed,
+            garbage_consumed,
             board,
             mv,
             move_dist,
@@ -225,18 +825,19 @@ impl Node {
             max_child_reward: 0,
             visits: 1,
             uses_hold,
-            finished: child_depth as usize >= data.queue.len()
+            finished: !self.speculate && child_depth as usize >= self.queue.len(),
+            expanding: false
         };
-        let (value, reward) = data.evaluator.evaluate(&child, &data.queue);
+        let (value, reward) = evaluator.evaluate(&child, &self.queue);
         child.value = value;
         child.reward = reward;
-        self.children.push(child);
-    }
-    fn advance(&mut self, pieces_used: u32){
-        self.finished = false;
-        self.depth -= pieces_used;
-        for c in self.children.iter_mut() {
-            c.advance(pieces_used);
+        if let Some(sibling) = children.iter_mut().find(|c| c.hash == child.hash) {
+            if child.total_value() > sibling.total_value() {
+                *sibling = child;
+            }
+        } else {
+            children.push(child);
         }
     }
 }
+
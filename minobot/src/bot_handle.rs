@@ -1,16 +1,21 @@
-use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, Condvar};
 use std::time::{Instant, Duration};
 use std::collections::VecDeque;
 
 use minotetris::*;
-use crate::bot::{Bot, BotSettings};
+use enumset::EnumSet;
+use crate::bot::{Bot, BotSettings, GarbageBatch, ThinkBudget};
 use crate::evaluator::Evaluator;
 use crate::pathfinder::{Moves, PathfinderMove};
 
 enum BotCommand {
     NewPiece(PieceType),
+    SetBag(EnumSet<PieceType>),
     Reset(Board),
-    BeginThinking,
+    AddGarbage(GarbageBatch),
+    ClearGarbage,
+    BeginThinking(ThinkBudget),
     NextMove,
 }
 
@@ -27,54 +32,174 @@ pub struct BotHandle {
     rx: Receiver<Option<BotMove>>
 }
 
+struct SearchState<E> {
+    bot: Bot<E>,
+    thinking: bool,
+    expanding: u32,
+    thinks: u32,
+    nodes: u32,
+    budget: ThinkBudget,
+    thinking_start: Instant,
+    /// Set when a worker found nothing to expand.
+    idle: bool,
+    quit: bool
+}
+
+impl<E> SearchState<E> {
+    fn budget_exhausted(&self) -> bool {
+        self.budget.exhausted(self.thinks, self.nodes, self.thinking_start.elapsed())
+    }
+}
+
+struct Search<E> {
+    state: Mutex<SearchState<E>>,
+    changed: Condvar
+}
+
+impl<E: Evaluator> Search<E> {
+    fn lock(&self) -> MutexGuard<'_, SearchState<E>> {
+        self.state.lock().unwrap()
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, SearchState<E>>) -> MutexGuard<'a, SearchState<E>> {
+        self.changed.wait(state).unwrap()
+    }
+
+    fn wait_idle<'a>(&self, mut state: MutexGuard<'a, SearchState<E>>) -> MutexGuard<'a, SearchState<E>> {
+        while state.expanding > 0 {
+            state = self.wait(state);
+        }
+        state
+    }
+
+    /// Waits for in-flight expansions to finish before changing the tree, since their paths
+    /// and queue snapshots would otherwise go stale, then resumes thinking.
+    fn paused(&self, mut state: MutexGuard<'_, SearchState<E>>, f: impl FnOnce(&mut Bot<E>)) {
+        let thinking = state.thinking;
+        state.thinking = false;
+        state = self.wait_idle(state);
+        f(&mut state.bot);
+        state.thinking = thinking;
+        state.idle = false;
+        self.changed.notify_all();
+    }
+
+    fn work(&self) {
+        let evaluator = self.lock().bot.evaluator();
+        let mut state = self.lock();
+        while !state.quit {
+            if state.thinking && state.budget_exhausted() {
+                state.thinking = false;
+                self.changed.notify_all();
+            }
+            let expansion = if state.thinking {
+                state.bot.select()
+            } else {
+                None
+            };
+            if let Some(expansion) = expansion {
+                state.idle = false;
+                state.expanding += 1;
+                drop(state);
+                let children = expansion.expand(&*evaluator);
+                state = self.lock();
+                state.nodes += state.bot.backup(expansion, children);
+                state.expanding -= 1;
+                state.thinks += 1;
+                if state.bot.root().finished {
+                    state.thinking = false;
+                }
+                self.changed.notify_all();
+            } else {
+                if state.bot.root().finished {
+                    state.thinking = false;
+                }
+                if state.thinking && !state.idle {
+                    state.idle = true;
+                    self.changed.notify_all();
+                }
+                state = self.wait(state);
+            }
+        }
+    }
+
+    /// With a counted budget, waits until it runs out so the move doesn't depend on timing.
+    fn wait_budget<'a>(&self, mut state: MutexGuard<'a, SearchState<E>>) -> MutexGuard<'a, SearchState<E>> {
+        if !state.budget.is_counted() {
+            return state;
+        }
+        while state.thinking && !(state.idle && state.expanding == 0) {
+            state = self.wait(state);
+        }
+        state
+    }
+}
+
 impl BotHandle {
     pub fn new(board: Board, evaluator: impl Evaluator + Send + 'static, settings: BotSettings) -> Self {
         let (tx, bot_rx) = channel::<BotCommand>();
         let (bot_tx, rx) = channel();
         std::thread::spawn(move || {
-            let mut thinking = false;
-            let mut thinking_start = Instant::now();
-            let mut thinks = 0;
-            let mut bot = Bot::new(board, evaluator, settings);
-            loop {
-                let command = if thinking {
-                    thinking = !bot.think();
-                    thinks += 1;
-                    match bot_rx.try_recv() {
-                        Ok(command) => command,
-                        Err(TryRecvError::Empty) => continue,
-                        _ => return
-                    }
-                } else if let Ok(command) = bot_rx.recv() {
-                    command
-                } else {
-                    return
-                };
+            let threads = settings.threads.max(1);
+            let search = Arc::new(Search {
+                state: Mutex::new(SearchState {
+                    bot: Bot::new(board, evaluator, settings),
+                    thinking: false,
+                    expanding: 0,
+                    thinks: 0,
+                    nodes: 0,
+                    budget: ThinkBudget::default(),
+                    thinking_start: Instant::now(),
+                    idle: false,
+                    quit: false
+                }),
+                changed: Condvar::new()
+            });
+            for _ in 0..threads {
+                let search = search.clone();
+                std::thread::spawn(move || search.work());
+            }
+            while let Ok(command) = bot_rx.recv() {
+                let mut state = search.lock();
                 match command {
-                    BotCommand::BeginThinking => {
-                        thinking_start = Instant::now();
-                        thinking = true;
+                    BotCommand::BeginThinking(budget) => {
+                        state.thinking_start = Instant::now();
+                        state.budget = budget;
+                        state.thinking = true;
+                        state.idle = false;
+                        search.changed.notify_all();
                     }
-                    BotCommand::NewPiece(piece) => bot.update_queue(piece),
-                    BotCommand::Reset(board) => bot.reset(board),
+                    BotCommand::NewPiece(piece) => search.paused(state, |bot| bot.update_queue(piece)),
+                    BotCommand::SetBag(bag) => search.paused(state, |bot| bot.set_bag(bag)),
+                    BotCommand::Reset(board) => search.paused(state, |bot| bot.reset(board)),
+                    BotCommand::AddGarbage(batch) => search.paused(state, |bot| bot.add_garbage(batch)),
+                    BotCommand::ClearGarbage => search.paused(state, |bot| bot.clear_garbage()),
                     BotCommand::NextMove => {
-                        thinking = false;
-                        let board = bot.root.board.clone();
-                        let mv = bot.next_move().map(|node| {
-                            let piece = Piece::spawn(&board, node.mv.kind);
+                        state = search.wait_budget(state);
+                        state.thinking = false;
+                        state = search.wait_idle(state);
+                        let thinks = state.thinks;
+                        let think_time = state.thinking_start.elapsed();
+                        let board = state.bot.root().board.clone();
+                        let rotate_180 = state.bot.data.settings.rotate_180;
+                        let mv = state.bot.next_move().map(|node| {
+                            let piece = Piece::spawn(&board, node.mv.kind, node.mv.system, node.mv.spin_rule);
                             BotMove {
                                 mv: node.mv,
                                 uses_hold: node.uses_hold,
-                                path: Moves::moves(&board, piece).path(node.mv),
-                                think_time: thinking_start.elapsed(),
+                                path: Moves::moves(&board, piece, rotate_180).path(node.mv),
+                                think_time,
                                 thinks
                             }
                         });
-                        thinks = 0;
+                        state.thinks = 0;
+                        state.nodes = 0;
                         bot_tx.send(mv).unwrap()
                     },
                 }
             }
+            search.lock().quit = true;
+            search.changed.notify_all();
         });
         Self {
             tx,
@@ -86,12 +211,31 @@ impl BotHandle {
         self.tx.send(BotCommand::NewPiece(piece)).unwrap();
     }
 
+    /// See `Bot::set_bag`.
+    pub fn set_bag(&self, bag: EnumSet<PieceType>) {
+        self.tx.send(BotCommand::SetBag(bag)).unwrap();
+    }
+
     pub fn reset(&self, board: Board) {
         self.tx.send(BotCommand::Reset(board)).unwrap();
     }
 
+    pub fn add_garbage(&self, batch: GarbageBatch) {
+        self.tx.send(BotCommand::AddGarbage(batch)).unwrap();
+    }
+
+    pub fn clear_garbage(&self) {
+        self.tx.send(BotCommand::ClearGarbage).unwrap();
+    }
+
     pub fn begin_thinking(&self) {
-        self.tx.send(BotCommand::BeginThinking).unwrap();
+        self.begin_thinking_with(ThinkBudget::default());
+    }
+
+    /// Thinks until `budget` runs out. If it is counted in thinks or nodes,
+    /// `next_move` waits for it to run out as well.
+    pub fn begin_thinking_with(&self, budget: ThinkBudget) {
+        self.tx.send(BotCommand::BeginThinking(budget)).unwrap();
     }
 
     pub fn next_move(&self) -> Option<BotMove> {
@@ -3,11 +3,12 @@ use serde::{ Serialize, Deserialize };
 use crate::bot::Node;
 use minotetris::*;
 
-pub trait Evaluator: Send {
+pub trait Evaluator: Send + Sync {
     fn evaluate(&self, node: &Node, queue: &[PieceType]) -> (i32, i32);
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StandardEvaluator {
     pub holes: i32,
     pub holes_sq: i32,
@@ -27,10 +28,19 @@ pub struct StandardEvaluator {
     pub line_clear: [i32; 5],
     pub mini_clear: [i32; 3],
     pub tspin_clear: [i32; 4],
+    /// Mini spins by pieces other than T, under `SpinRule::AllMini`.
+    pub mini_spin_clear: [i32; 5],
+    /// Full spins by pieces other than T, under `SpinRule::AllSpin`.
+    pub spin_clear: [i32; 5],
     pub perfect_clear: i32,
     pub combo_garbage: i32,
     pub wasted_t: i32,
-    pub tslot: i32
+    pub tslot: i32,
+    /// Reward for each line sent. Unless this is 0, it replaces the clear tables and `combo_garbage`,
+    /// so the game's `AttackRules` decide what a clear is worth.
+    pub garbage_sent: i32,
+    pub garbage_cancelled: i32,
+    pub pending_garbage: i32
 }
 
 
@@ -71,10 +81,27 @@ impl Default for  StandardEvaluator {
                 629,
                 1244
             ],
+            mini_spin_clear: [
+                7,
+                -363,
+                -293,
+                -280,
+                554
+            ],
+            spin_clear: [
+                -6,
+                108,
+                629,
+                1244,
+                1244
+            ],
             perfect_clear: 5000,
             combo_garbage: 305,
             wasted_t: -268,
-            tslot: 301
+            tslot: 301,
+            garbage_sent: 0,
+            garbage_cancelled: 60,
+            pending_garbage: -30
         }
     }
 }
@@ -185,17 +212,26 @@ impl Evaluator for StandardEvaluator {
         if node.mv.kind == PieceType::T && (node.mv.tspin == TspinType::None || node.lock.lines_cleared == 0) {
             reward += self.wasted_t;
         }
-        reward += match node.mv.tspin {
-            TspinType::None => &self.line_clear[..],
-            TspinType::Mini => &self.mini_clear[..],
-            TspinType::Full => &self.tspin_clear[..],
-        }[node.lock.lines_cleared as usize];
-        reward += COMBO_TABLE[(node.lock.combo as usize).min(COMBO_TABLE.len() - 1)] * self.combo_garbage;
+        if self.garbage_sent == 0 {
+            let is_t = node.mv.kind == PieceType::T;
+            reward += match node.mv.tspin {
+                TspinType::None => &self.line_clear[..],
+                TspinType::Mini if is_t => &self.mini_clear[..],
+                TspinType::Full if is_t => &self.tspin_clear[..],
+                TspinType::Mini => &self.mini_spin_clear[..],
+                TspinType::Full => &self.spin_clear[..]
+            }[node.lock.lines_cleared as usize];
+            reward += COMBO_TABLE[(node.lock.combo as usize).min(COMBO_TABLE.len() - 1)] * self.combo_garbage;
+        }
         reward += node.move_dist * self.move_dist;
         if node.board.column_heights().iter().all(|&h| h == 0) {
             reward += self.perfect_clear;
         }
 
+        reward += (node.attack - node.garbage_cancelled) as i32 * self.garbage_sent;
+        reward += node.garbage_cancelled as i32 * self.garbage_cancelled;
+        value += node.garbage_pending as i32 * self.pending_garbage;
+
         (value, reward)
     }
 }
@@ -2,4 +2,6 @@ pub mod pathfinder;
 pub mod bot;
 pub mod bot_handle;
 pub mod evaluator;
+pub mod transposition;
+pub mod arena;
 pub use bot_handle::{BotHandle, BotMove};
@@ -7,6 +7,7 @@ pub enum PathfinderMove {
     Right,
     RotLeft,
     RotRight,
+    Rot180,
     SonicDrop
 }
 
@@ -18,6 +19,15 @@ const MOVES: &[PathfinderMove] = &[
     PathfinderMove::SonicDrop
 ];
 
+const MOVES_180: &[PathfinderMove] = &[
+    PathfinderMove::RotLeft,
+    PathfinderMove::RotRight,
+    PathfinderMove::Rot180,
+    PathfinderMove::Left,
+    PathfinderMove::Right,
+    PathfinderMove::SonicDrop
+];
+
 const O_MOVES: &[PathfinderMove] = &[
     PathfinderMove::Left,
     PathfinderMove::Right,
@@ -25,14 +35,16 @@ const O_MOVES: &[PathfinderMove] = &[
 ];
 
 pub struct Moves {
-    field: [[[[Option<MoveNode>; 3]; 4]; 40]; 10],
+    /// Indexed by `x + 1`, since some ARS states sit entirely right of the piece's center.
+    field: [[[[Option<MoveNode>; 3]; 4]; 40]; 11],
     pub moves: Vec<(Piece, i32)>
 }
 
 impl Moves {
-    pub fn moves(board: &Board, piece: Piece) -> Self {
+    /// `rotate_180` is the kick table for 180 rotations, or `None` if they aren't allowed.
+    pub fn moves(board: &Board, piece: Piece, rotate_180: Option<Kicks180>) -> Self {
         let mut this = Self {
-            field: [[[[None; 3]; 4]; 40]; 10],
+            field: [[[[None; 3]; 4]; 40]; 11],
             moves: Vec::new(),
         };
         let mut locks = HashMap::with_capacity(1024);
@@ -47,6 +59,8 @@ impl Moves {
         while let Some(parent) = queue.pop_front() {
             let moves = if piece.kind == PieceType::O {
                 O_MOVES
+            } else if rotate_180.is_some() {
+                MOVES_180
             } else {
                 MOVES
             };
@@ -58,6 +72,7 @@ impl Moves {
                     PathfinderMove::Right => child.move_right(board),
                     PathfinderMove::RotLeft => child.turn_left(board),
                     PathfinderMove::RotRight => child.turn_right(board),
+                    PathfinderMove::Rot180 => child.turn_180(board, rotate_180.unwrap()),
                     PathfinderMove::SonicDrop => child.sonic_drop(board)
                 };
                 if success {
@@ -100,13 +115,15 @@ impl Moves {
             .values()
             .map(|&mv| (mv, this.get(mv).unwrap().true_dist()))
             .collect();
+        // HashMap order changes from run to run, which would make the whole search irreproducible.
+        this.moves.sort_by_key(|&(mv, _)| (mv.x, mv.y, mv.r, mv.tspin as u8));
         this
     }
     fn get(&self, state: Piece) -> &Option<MoveNode> {
-        &self.field[state.x as usize][state.y as usize][state.r as usize][state.tspin as usize]
+        &self.field[(state.x + 1) as usize][state.y as usize][state.r as usize][state.tspin as usize]
     }
     fn get_mut(&mut self, state: Piece) -> &mut Option<MoveNode> {
-        &mut self.field[state.x as usize][state.y as usize][state.r as usize][state.tspin as usize]
+        &mut self.field[(state.x + 1) as usize][state.y as usize][state.r as usize][state.tspin as usize]
     }
     pub fn path(&self, state: Piece) -> VecDeque<PathfinderMove> {
         let mut path = VecDeque::new();
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use enumset::EnumSet;
+
+use crate::bot::Node;
+use minotetris::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TranspositionEntry {
+    pub value: i32,
+    pub max_child_reward: i32,
+    pub visits: u32,
+    pub depth: u32
+}
+
+#[derive(Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, TranspositionEntry>,
+    lookups: u64,
+    hits: u64
+}
+
+impl TranspositionTable {
+    pub fn key(board: &Board, depth: u32) -> u64 {
+        board.zobrist_hash() ^ (depth as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15)
+    }
+    /// Key for a position past the end of the queue, whose future also depends on the bag.
+    pub fn speculative_key(board: &Board, depth: u32, bag: EnumSet<PieceType>) -> u64 {
+        let bits = bag.iter().fold(0u64, |bits, piece| bits | 1 << piece as u64);
+        Self::key(board, depth) ^ (bits + 1).wrapping_mul(0xC2B2AE3D27D4EB4F)
+    }
+    /// Mixed into the key of a position that has already dealt with some incoming garbage.
+    pub fn garbage_key(consumed: u32) -> u64 {
+        (consumed as u64).wrapping_mul(0x165667B19E3779F9)
+    }
+    pub fn get(&mut self, key: u64) -> Option<TranspositionEntry> {
+        self.lookups += 1;
+        let entry = self.entries.get(&key).copied();
+        if entry.is_some() {
+            self.hits += 1;
+        }
+        entry
+    }
+    pub fn store(&mut self, key: u64, depth: u32, node: &Node) {
+        let entry = TranspositionEntry {
+            value: node.value,
+            max_child_reward: node.max_child_reward,
+            visits: node.visits,
+            depth
+        };
+        self.entries
+            .entry(key)
+            .and_modify(|prev| if entry.visits >= prev.visits {
+                *prev = entry;
+            })
+            .or_insert(entry);
+    }
+    pub fn retain_from(&mut self, depth: u32) {
+        self.entries.retain(|_, e| e.depth >= depth);
+    }
+    /// Drops the least visited entries until at most `max` are left.
+    pub fn shrink_to(&mut self, max: usize) {
+        if self.entries.len() <= max {
+            return;
+        }
+        let mut order: Vec<_> = self.entries.iter().map(|(&key, e)| (e.visits, key)).collect();
+        // Sorting by key too keeps which entries go independent of the map's iteration order.
+        order.sort_unstable();
+        for &(_, key) in &order[..order.len() - max] {
+            self.entries.remove(&key);
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn lookups(&self) -> u64 {
+        self.lookups
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use minotetris::*;
+use minobot::bot::{Bot, BotSettings, draw_from_bag};
+use minobot::evaluator::StandardEvaluator;
+use enumset::EnumSet;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+fn speculating_bot(queue: &[PieceType]) -> Bot {
+    let settings = BotSettings {
+        speculate: true,
+        ..BotSettings::default()
+    };
+    let mut bot = Bot::new(Board::new(), StandardEvaluator::default(), settings);
+    for &piece in queue {
+        bot.update_queue(piece);
+    }
+    bot
+}
+
+/// Thinks until the tree is finished, giving up after `limit` thinks.
+fn finishes_within(bot: &mut Bot, limit: u32) -> bool {
+    (0..limit).any(|_| bot.think())
+}
+
+#[test]
+fn bag_follows_the_queue() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut queue = PieceQueue::new(5, RandomizerKind::Bag7.build(), &mut rng);
+    let mut bot = speculating_bot(&queue.get_queue().iter().copied().collect::<Vec<_>>());
+    assert_eq!(bot.data.bag, None);
+    bot.set_bag(queue.bag_state().unwrap());
+    for _ in 0..30 {
+        queue.next(&mut rng);
+        bot.update_queue(*queue.get_queue().back().unwrap());
+        assert_eq!(bot.data.bag, queue.bag_state());
+    }
+}
+
+#[test]
+fn unknown_bag_stops_speculation() {
+    let mut bot = speculating_bot(&[PieceType::T, PieceType::O]);
+    assert!(finishes_within(&mut bot, 5000));
+
+    let mut bot = speculating_bot(&[PieceType::T, PieceType::O]);
+    bot.set_bag(EnumSet::all() - PieceType::T - PieceType::O);
+    assert!(!finishes_within(&mut bot, 5000));
+}
+
+#[test]
+fn piece_outside_the_bag_forgets_it() {
+    let mut bot = speculating_bot(&[PieceType::T]);
+    bot.set_bag(EnumSet::all() - PieceType::T);
+    bot.update_queue(PieceType::T);
+    assert_eq!(bot.data.bag, None);
+
+    assert_eq!(draw_from_bag(EnumSet::new(), PieceType::I), Some(EnumSet::all() - PieceType::I));
+    assert_eq!(draw_from_bag(EnumSet::only(PieceType::I), PieceType::O), None);
+}
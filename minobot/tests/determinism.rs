@@ -0,0 +1,57 @@
+use minotetris::*;
+use minobot::bot::{Bot, BotSettings};
+use minobot::evaluator::StandardEvaluator;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Plays `pieces` moves with a fixed number of thinks per move, like `stats` does.
+fn play(seed: u64, settings: BotSettings, pieces: u32, thinks: u32) -> Vec<(Piece, bool)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut queue = PieceQueue::new(5, RandomizerKind::Bag7.build(), &mut rng);
+    let mut bot = Bot::new(Board::new(), StandardEvaluator::default(), settings);
+    for &piece in queue.get_queue() {
+        bot.update_queue(piece);
+    }
+    bot.set_bag(queue.bag_state().unwrap());
+    let mut moves = Vec::new();
+    for _ in 0..pieces {
+        for _ in 0..thinks {
+            bot.think();
+        }
+        let prev_hold = bot.root().board.hold.is_some();
+        let node = bot.next_move().unwrap();
+        moves.push((node.mv, node.uses_hold));
+        let pieces_used = if !prev_hold && node.uses_hold { 2 } else { 1 };
+        for _ in 0..pieces_used {
+            queue.next(&mut rng);
+            bot.update_queue(*queue.get_queue().back().unwrap());
+        }
+    }
+    moves
+}
+
+#[test]
+fn same_seed_same_moves() {
+    let first = play(42, BotSettings::default(), 40, 200);
+    let second = play(42, BotSettings::default(), 40, 200);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn same_seed_same_moves_with_speculation_and_pruning() {
+    let settings = BotSettings {
+        speculate: true,
+        max_nodes: Some(2000),
+        ..BotSettings::default()
+    };
+    let first = play(7, settings.clone(), 40, 200);
+    let second = play(7, settings, 40, 200);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn different_seeds_different_moves() {
+    let first = play(1, BotSettings::default(), 40, 100);
+    let second = play(2, BotSettings::default(), 40, 100);
+    assert_ne!(first, second);
+}
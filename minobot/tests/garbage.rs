@@ -0,0 +1,37 @@
+use minotetris::*;
+use minobot::bot::{Bot, BotSettings, GarbageBatch};
+use minobot::evaluator::StandardEvaluator;
+
+fn bot_with_garbage(delay: u32) -> Bot {
+    let mut bot = Bot::new(Board::new(), StandardEvaluator::default(), BotSettings::default());
+    for &piece in &[PieceType::T, PieceType::I, PieceType::O, PieceType::S] {
+        bot.update_queue(piece);
+    }
+    bot.add_garbage(GarbageBatch {
+        lines: 2,
+        hole: Some(0),
+        delay
+    });
+    bot
+}
+
+#[test]
+fn first_hold_is_not_a_placement() {
+    let mut bot = bot_with_garbage(1);
+    bot.think();
+    let root = bot.root();
+    assert!(root.children.iter().any(|&c| bot.nodes[c].uses_hold));
+    for &c in &root.children {
+        assert_eq!(bot.nodes[c].garbage_received, 0);
+    }
+}
+
+#[test]
+fn delay_counts_placements() {
+    let mut bot = bot_with_garbage(3);
+    for _ in 0..100 {
+        bot.think();
+    }
+    bot.next_move().unwrap();
+    assert_eq!(bot.data.garbage[0].delay, 2);
+}
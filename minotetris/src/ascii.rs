@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::*;
+
+/// A board with an optional falling piece, written as a grid of text like
+///
+/// ```text
+/// hold: T
+/// combo: 1
+/// b2b: false
+/// ....ttt...
+/// ##L..t.###
+/// ###OO.####
+/// ```
+///
+/// Rows are listed from the top, down to the bottom of the board. Pieces that
+/// have locked are shown by their uppercase letter and the falling piece by its
+/// lowercase letter. `#` is garbage, `@` is solid and `.` is empty. When parsing,
+/// the header lines are optional and rows above the ones listed are empty.
+#[derive(Debug, Clone)]
+pub struct Position<R=ColoredRow> {
+    pub board: Board<R>,
+    pub piece: Option<Piece>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePositionError {
+    InvalidHeader(String),
+    InvalidRow(String),
+    TooManyRows,
+    /// The lowercase cells aren't exactly one piece.
+    InvalidPiece
+}
+
+impl fmt::Display for ParsePositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidHeader(line) => write!(f, "invalid header {:?}", line),
+            Self::InvalidRow(line) => write!(f, "invalid row {:?}", line),
+            Self::TooManyRows => write!(f, "more than 40 rows"),
+            Self::InvalidPiece => write!(f, "the falling piece isn't a piece")
+        }
+    }
+}
+
+impl std::error::Error for ParsePositionError {}
+
+pub(crate) fn cell_char(cell: CellType) -> char {
+    match cell {
+        CellType::Empty => '.',
+        CellType::Garbage => '#',
+        CellType::Solid => '@',
+        CellType::J => 'J',
+        CellType::L => 'L',
+        CellType::S => 'S',
+        CellType::T => 'T',
+        CellType::Z => 'Z',
+        CellType::I => 'I',
+        CellType::O => 'O'
+    }
+}
+
+pub(crate) fn char_cell(c: char) -> Option<CellType> {
+    Some(match c {
+        '.' => CellType::Empty,
+        '#' => CellType::Garbage,
+        '@' => CellType::Solid,
+        c => PieceType::from_name(c)?.cell()
+    })
+}
+
+fn write_board<R: Row>(f: &mut fmt::Formatter, board: &Board<R>, piece: Option<Piece>) -> fmt::Result {
+    let hold = board.hold.map_or('-', PieceType::name);
+    writeln!(f, "hold: {}", hold)?;
+    writeln!(f, "combo: {}", board.combo)?;
+    write!(f, "b2b: {}", board.b2b)?;
+    let cells = piece.map(|piece| piece.cells());
+    let height = board.column_heights()
+        .iter()
+        .copied()
+        .chain(cells.iter().flatten().map(|&(_, y)| y + 1))
+        .max()
+        .unwrap();
+    for y in (0..height).rev() {
+        writeln!(f)?;
+        for x in 0..10 {
+            let c = match (cells, piece) {
+                (Some(cells), Some(piece)) if cells.contains(&(x, y)) => piece.kind.name().to_ascii_lowercase(),
+                _ => cell_char(board.rows()[y as usize].cell_type(x as usize))
+            };
+            write!(f, "{}", c)?;
+        }
+    }
+    Ok(())
+}
+
+impl<R: Row> fmt::Display for Position<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_board(f, &self.board, self.piece)
+    }
+}
+
+impl<R: Row> FromStr for Position<R> {
+    type Err = ParsePositionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut board = Board::<R>::new();
+        let mut rows = Vec::new();
+        let mut piece_cells = Vec::new();
+        let mut piece_kind = None;
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some((key, value)) = line.split_once(':') {
+                let invalid = || ParsePositionError::InvalidHeader(line.to_owned());
+                let value = value.trim();
+                match key.trim() {
+                    "hold" if value == "-" => board.hold = None,
+                    "hold" => {
+                        let mut chars = value.chars();
+                        board.hold = chars.next().and_then(PieceType::from_name);
+                        if board.hold.is_none() || chars.next().is_some() {
+                            return Err(invalid());
+                        }
+                    }
+                    "combo" => board.combo = value.parse().map_err(|_| invalid())?,
+                    "b2b" => board.b2b = value.parse().map_err(|_| invalid())?,
+                    _ => return Err(invalid())
+                }
+                continue;
+            }
+            let invalid = || ParsePositionError::InvalidRow(line.to_owned());
+            if line.chars().count() != 10 {
+                return Err(invalid());
+            }
+            let mut row = R::default();
+            for (x, c) in line.chars().enumerate() {
+                if c.is_ascii_lowercase() {
+                    let kind = PieceType::from_name(c.to_ascii_uppercase()).ok_or_else(invalid)?;
+                    if piece_kind.replace(kind).is_some_and(|other| other != kind) {
+                        return Err(ParsePositionError::InvalidPiece);
+                    }
+                    piece_cells.push((x as i32, rows.len()));
+                } else {
+                    row.set(x, char_cell(c).ok_or_else(invalid)?);
+                }
+            }
+            rows.push(row);
+        }
+        if rows.len() > 40 {
+            return Err(ParsePositionError::TooManyRows);
+        }
+
+        // Rows were listed from the top.
+        let height = rows.len() as i32;
+        let piece = match piece_kind {
+            Some(kind) => {
+                if piece_cells.len() != 4 {
+                    return Err(ParsePositionError::InvalidPiece);
+                }
+                let mut cells = [(0, 0); 4];
+                for (cell, &(x, row)) in cells.iter_mut().zip(&piece_cells) {
+                    *cell = (x, height - 1 - row as i32);
+                }
+                Some(Piece::from_cells(kind, cells, 0).ok_or(ParsePositionError::InvalidPiece)?)
+            }
+            None => None
+        };
+        rows.reverse();
+        rows.resize(40, R::default());
+        board.set_field(rows.into_iter().collect::<arrayvec::ArrayVec<[R; 40]>>());
+        Ok(Self { board, piece })
+    }
+}
+
+impl<R: Row> fmt::Display for Board<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_board(f, self, None)
+    }
+}
+
+impl<R: Row> FromStr for Board<R> {
+    type Err = ParsePositionError;
+
+    /// Parses a board written without a falling piece.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let position: Position<R> = s.parse()?;
+        if position.piece.is_some() {
+            return Err(ParsePositionError::InvalidPiece);
+        }
+        Ok(position.board)
+    }
+}
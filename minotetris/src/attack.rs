@@ -0,0 +1,78 @@
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize };
+
+use crate::*;
+
+/// How many garbage lines a placement sends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct AttackRules {
+    /// Lines sent by clearing 1 to 4 lines.
+    pub line_clear: [u32; 4],
+    /// Lines sent by T-spin singles, doubles and triples.
+    /// Full spins by other pieces use this too, with longer clears sending like regular ones.
+    pub tspin: [u32; 3],
+    /// Lines sent by T-spin mini singles and doubles.
+    /// Mini spins by other pieces send like regular clears.
+    pub tspin_mini: [u32; 2],
+    pub b2b_bonus: u32,
+    /// Extra lines indexed by the number of clears in a row before this one.
+    /// The last entry is used for any longer combo.
+    pub combo: Vec<u32>,
+    pub perfect_clear: u32
+}
+
+impl Default for AttackRules {
+    fn default() -> Self {
+        AttackRules {
+            line_clear: [0, 1, 2, 4],
+            tspin: [0, 2, 4],
+            tspin_mini: [0, 1],
+            b2b_bonus: 1,
+            combo: Vec::new(),
+            perfect_clear: 0
+        }
+    }
+}
+
+impl AttackRules {
+    /// The attack table used by most modern guideline games.
+    pub fn guideline() -> Self {
+        AttackRules {
+            line_clear: [0, 1, 2, 4],
+            tspin: [2, 4, 6],
+            tspin_mini: [0, 1],
+            b2b_bonus: 1,
+            combo: vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            perfect_clear: 10
+        }
+    }
+
+    pub fn attack(&self, lock: &LockResult) -> u32 {
+        if lock.lines_cleared == 0 {
+            return 0;
+        }
+        let lines = lock.lines_cleared as usize - 1;
+        let table = match lock.spin {
+            TspinType::Mini if lock.piece == PieceType::T => &self.tspin_mini[..],
+            TspinType::Full => &self.tspin[..],
+            _ => &self.line_clear[..]
+        };
+        let mut attack = table
+            .get(lines)
+            .or_else(|| self.line_clear.get(lines))
+            .copied()
+            .unwrap_or(0);
+        if lock.b2b_bonus {
+            attack += self.b2b_bonus;
+        }
+        if let Some(&last) = self.combo.last() {
+            let combo = lock.combo as usize - 1;
+            attack += self.combo.get(combo).copied().unwrap_or(last);
+        }
+        if lock.perfect_clear {
+            attack += self.perfect_clear;
+        }
+        attack
+    }
+}
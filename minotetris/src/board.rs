@@ -1,12 +1,18 @@
 use crate::*;
 use arrayvec::ArrayVec;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize, Serializer, Deserializer, de::Error };
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LockResult {
+    pub piece: PieceType,
+    pub spin: TspinType,
     pub lines_cleared: i32,
     pub block_out: bool,
     pub combo: u32,
-    pub b2b_bonus: bool
+    pub b2b_bonus: bool,
+    pub perfect_clear: bool
 }
 
 pub trait Row: Copy + Default {
@@ -71,6 +77,30 @@ impl Row for ColoredRow {
     }
 }
 
+/// Written as a row of the ASCII board format, like `JJJ.IIII##`.
+#[cfg(feature = "serde")]
+impl Serialize for ColoredRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let row: String = self.row.iter().map(|&cell| cell_char(cell)).collect();
+        serializer.serialize_str(&row)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ColoredRow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let mut row = ColoredRow::default();
+        if text.chars().count() != 10 {
+            return Err(D::Error::custom(format!("row {:?} isn't 10 cells wide", text)));
+        }
+        for (cell, c) in row.row.iter_mut().zip(text.chars()) {
+            *cell = char_cell(c).ok_or_else(|| D::Error::custom(format!("invalid cell {:?}", c)))?;
+        }
+        Ok(row)
+    }
+}
+
 impl Default for ColoredRow {
     fn default() -> Self {
         ColoredRow {
@@ -134,10 +164,13 @@ impl<R: Row> Board<R> {
         }
 
         LockResult {
+            piece: piece.kind,
+            spin: piece.tspin,
             lines_cleared,
             block_out,
             combo: self.combo,
-            b2b_bonus
+            b2b_bonus,
+            perfect_clear: lines_cleared > 0 && self.column_heights.iter().all(|&h| h == 0)
         }
     }
     pub fn piece_fits(&self, piece: Piece) -> bool {
@@ -199,4 +232,56 @@ impl Board<ColoredRow> {
             b2b: self.b2b
         }
     }
-}
\ No newline at end of file
+}
+
+/// How boards are serialized. Only rows up to the highest filled one are stored,
+/// so a `Board<u16>` is a short list of numbers.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Board")]
+struct BoardData<R> {
+    /// Rows from the bottom up.
+    rows: Vec<R>,
+    #[serde(default)]
+    hold: Option<PieceType>,
+    #[serde(default)]
+    combo: u32,
+    #[serde(default)]
+    b2b: bool
+}
+
+#[cfg(feature = "serde")]
+impl<R: Row + Serialize> Serialize for Board<R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let height = self.rows
+            .iter()
+            .rposition(|row| (0..10).any(|x| row.get(x)))
+            .map_or(0, |y| y + 1);
+        BoardData {
+            rows: self.rows[..height].to_vec(),
+            hold: self.hold,
+            combo: self.combo,
+            b2b: self.b2b
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, R: Row + Deserialize<'de>> Deserialize<'de> for Board<R> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = BoardData::<R>::deserialize(deserializer)?;
+        if data.rows.len() > 40 {
+            return Err(D::Error::custom("boards have at most 40 rows"));
+        }
+        let mut rows: ArrayVec<[R; 40]> = data.rows.into_iter().collect();
+        while !rows.is_full() {
+            rows.push(R::default());
+        }
+        let mut board = Board::new();
+        board.set_field(rows);
+        board.hold = data.hold;
+        board.combo = data.combo;
+        board.b2b = data.b2b;
+        Ok(board)
+    }
+}
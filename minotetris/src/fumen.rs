@@ -0,0 +1,524 @@
+use crate::*;
+
+/// One page of a fumen.
+#[derive(Debug, Clone)]
+pub struct FumenPage<R=ColoredRow> {
+    /// The field before `piece` is placed. Only the bottom 23 rows are kept.
+    /// `board.hold` is stored in the comment as a quiz, like `#Q=[T](I)OSZ`.
+    pub board: Board<R>,
+    pub piece: Option<Piece>,
+    /// Locks `piece` and clears lines to get the field of the next page.
+    pub lock: bool,
+    /// The current piece followed by the next pieces, stored in the comment along with the hold.
+    pub queue: Vec<PieceType>,
+    pub comment: String
+}
+
+impl<R: Row> FumenPage<R> {
+    pub fn new(board: Board<R>) -> Self {
+        Self {
+            board,
+            piece: None,
+            lock: true,
+            queue: Vec::new(),
+            comment: String::new()
+        }
+    }
+
+    /// The field of the page after this one.
+    pub fn next_board(&self) -> Board<R> {
+        let mut board = self.board.clone();
+        if let (true, Some(piece)) = (self.lock, self.piece) {
+            board.lock_piece(piece);
+        }
+        board
+    }
+
+    fn quiz_comment(&self) -> String {
+        if self.board.hold.is_none() && self.queue.is_empty() {
+            return self.comment.clone();
+        }
+        let name = |piece: Option<&PieceType>| piece.map(|&p| p.name().to_string()).unwrap_or_default();
+        let mut text = format!("#Q=[{}]({})", name(self.board.hold.as_ref()), name(self.queue.first()));
+        text.extend(self.queue.iter().skip(1).map(|&p| p.name()));
+        if !self.comment.is_empty() {
+            text.push(';');
+            text.push_str(&self.comment);
+        }
+        text
+    }
+
+    fn set_quiz_comment(&mut self, text: &str) {
+        self.comment = text.to_owned();
+        if let Some(quiz) = text.strip_prefix("#Q=") {
+            let (quiz, comment) = quiz.split_once(';').unwrap_or((quiz, ""));
+            let mut chars = quiz.chars();
+            if chars.next() != Some('[') {
+                return;
+            }
+            let hold = chars.clone().next().and_then(PieceType::from_name);
+            let mut queue = Vec::new();
+            let rest: String = chars.skip(if hold.is_some() { 1 } else { 0 }).collect();
+            let rest = match rest.strip_prefix("](") {
+                Some(rest) => rest,
+                None => return
+            };
+            let mut chars = rest.chars();
+            for c in &mut chars {
+                match PieceType::from_name(c) {
+                    Some(piece) => queue.push(piece),
+                    None if c == ')' => break,
+                    None => return
+                }
+            }
+            for c in chars {
+                match PieceType::from_name(c) {
+                    Some(piece) => queue.push(piece),
+                    None => return
+                }
+            }
+            self.board.hold = hold;
+            self.queue = queue;
+            self.comment = comment.to_owned();
+        }
+    }
+}
+
+/// A sequence of pages in the v115 fumen format.
+#[derive(Debug, Clone)]
+pub struct Fumen<R=ColoredRow> {
+    pub pages: Vec<FumenPage<R>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FumenError {
+    UnsupportedVersion,
+    InvalidCharacter(char),
+    UnexpectedEnd,
+    InvalidValue,
+    /// A piece to encode is partly above the 23 rows fumen has room for.
+    PieceOutOfField
+}
+
+impl std::fmt::Display for FumenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedVersion => write!(f, "only v115 fumens are supported"),
+            Self::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            Self::UnexpectedEnd => write!(f, "fumen ended early"),
+            Self::InvalidValue => write!(f, "invalid value"),
+            Self::PieceOutOfField => write!(f, "piece is outside the fumen field")
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const FIELD_HEIGHT: usize = 23;
+/// Cells in the field plus the garbage row under it.
+const FIELD_CELLS: usize = (FIELD_HEIGHT + 1) * 10;
+
+/// Fumen's colors, indexed by row from the bottom with the garbage row last.
+type Field = [[u8; 10]; FIELD_HEIGHT + 1];
+const GARBAGE_ROW: usize = FIELD_HEIGHT;
+
+impl<R: Row> Fumen<R> {
+    /// Pages that lock each of `pieces` in turn, starting from `board`.
+    pub fn sequence(board: &Board<R>, pieces: impl IntoIterator<Item=Piece>) -> Self {
+        let mut board = board.clone();
+        let mut pages = Vec::new();
+        for piece in pieces {
+            let mut page = FumenPage::new(board);
+            page.piece = Some(piece);
+            board = page.next_board();
+            pages.push(page);
+        }
+        if pages.is_empty() {
+            pages.push(FumenPage::new(board));
+        }
+        Self { pages }
+    }
+
+    pub fn encode(&self) -> Result<String, FumenError> {
+        let mut values = Vec::new();
+        let mut prev: Field = [[0; 10]; FIELD_HEIGHT + 1];
+        let mut repeat_index = None;
+        let mut prev_comment = String::new();
+        for (i, page) in self.pages.iter().enumerate() {
+            let mut field: Field = [[0; 10]; FIELD_HEIGHT + 1];
+            for (colors, row) in field.iter_mut().take(FIELD_HEIGHT).zip(page.board.rows()) {
+                for (x, color) in colors.iter_mut().enumerate() {
+                    *color = cell_color(row.cell_type(x));
+                }
+            }
+
+            let changed = encode_field(&prev, &field, &mut values);
+            if changed {
+                repeat_index = None;
+            } else {
+                match repeat_index {
+                    Some(index) if values[index] < 63 => {
+                        // Only the repeat count of the first unchanged page is kept.
+                        values.truncate(values.len() - 2);
+                        values[index] += 1;
+                    }
+                    _ => {
+                        values.push(0);
+                        repeat_index = Some(values.len() - 1);
+                    }
+                }
+            }
+
+            let comment = page.quiz_comment();
+            let write_comment = comment != prev_comment;
+            let (kind, rotation, position) = match page.piece {
+                Some(piece) => encode_piece(piece)?,
+                None => (0, 0, 0)
+            };
+            let mut action = !page.lock as u32;
+            action = action * 2 + write_comment as u32;
+            action = action * 2 + (i == 0) as u32;
+            // Neither mirroring nor raising the garbage row are used.
+            action *= 2;
+            action *= 2;
+            action = action * FIELD_CELLS as u32 + position;
+            action = action * 4 + rotation;
+            action = action * 8 + kind;
+            push_values(&mut values, action, 3);
+
+            if write_comment {
+                let escaped = escape(&comment);
+                let escaped = &escaped.as_bytes()[..escaped.len().min(4095)];
+                push_values(&mut values, escaped.len() as u32, 2);
+                for chunk in escaped.chunks(4) {
+                    let mut value = 0;
+                    for &c in chunk.iter().rev() {
+                        let index = COMMENT_TABLE.iter().position(|&t| t == c).unwrap();
+                        value = value * (COMMENT_TABLE.len() as u32 + 1) + index as u32;
+                    }
+                    push_values(&mut values, value, 5);
+                }
+                prev_comment = comment;
+            }
+
+            prev = field;
+            if page.lock {
+                if let Some(piece) = page.piece {
+                    for (x, y) in piece.cells().iter().copied() {
+                        if y >= 0 && (y as usize) < FIELD_HEIGHT {
+                            prev[y as usize][x as usize] = cell_color(piece.kind.cell());
+                        }
+                    }
+                }
+                clear_lines(&mut prev);
+            }
+        }
+
+        let data: String = values.iter().map(|&v| ENCODE_TABLE[v as usize] as char).collect();
+        let mut fumen = String::from("v115@");
+        for (i, c) in data.chars().enumerate() {
+            // Fumen breaks the data into lines of 47 characters, counting the prefix.
+            if i >= 42 && (i - 42) % 47 == 0 {
+                fumen.push('?');
+            }
+            fumen.push(c);
+        }
+        Ok(fumen)
+    }
+
+    pub fn decode(fumen: &str) -> Result<Self, FumenError> {
+        let data = fumen.trim();
+        let data = data.rsplit_once("115@").ok_or(FumenError::UnsupportedVersion)?.1;
+        let mut values = Vec::new();
+        for c in data.chars() {
+            if c == '?' {
+                continue;
+            }
+            let value = ENCODE_TABLE.iter().position(|&t| t as char == c).ok_or(FumenError::InvalidCharacter(c))?;
+            values.push(value as u32);
+        }
+        let mut values = values.into_iter();
+        let mut poll = |count: u32| -> Result<u32, FumenError> {
+            let mut value = 0;
+            for i in 0..count {
+                value += values.next().ok_or(FumenError::UnexpectedEnd)? * 64u32.pow(i);
+            }
+            Ok(value)
+        };
+
+        let mut pages = Vec::new();
+        let mut field: Field = [[0; 10]; FIELD_HEIGHT + 1];
+        let mut repeats = 0;
+        let mut comment = String::new();
+        loop {
+            if repeats > 0 {
+                repeats -= 1;
+            } else {
+                let first = match poll(2) {
+                    Ok(value) => value,
+                    Err(FumenError::UnexpectedEnd) if !pages.is_empty() => break,
+                    Err(err) => return Err(err)
+                };
+                let mut index = 0;
+                let mut block = Some(first);
+                while index < FIELD_CELLS {
+                    let value = match block.take() {
+                        Some(value) => value,
+                        None => poll(2)?
+                    };
+                    let diff = value / FIELD_CELLS as u32;
+                    let count = value as usize % FIELD_CELLS + 1;
+                    if diff > 16 || index + count > FIELD_CELLS {
+                        return Err(FumenError::InvalidValue);
+                    }
+                    if diff == 8 && count == FIELD_CELLS {
+                        repeats = poll(1)?;
+                    }
+                    for i in index..index + count {
+                        let y = FIELD_HEIGHT as i32 - 1 - (i / 10) as i32;
+                        let row = if y < 0 { GARBAGE_ROW } else { y as usize };
+                        let cell = &mut field[row][i % 10];
+                        let color = *cell as i32 + diff as i32 - 8;
+                        if !(0..=8).contains(&color) {
+                            return Err(FumenError::InvalidValue);
+                        }
+                        *cell = color as u8;
+                    }
+                    index += count;
+                }
+            }
+
+            let mut action = poll(3)?;
+            let kind = action % 8;
+            action /= 8;
+            let rotation = action % 4;
+            action /= 4;
+            let position = action % FIELD_CELLS as u32;
+            action /= FIELD_CELLS as u32;
+            let rise = action % 2 == 1;
+            action /= 2;
+            let mirror = action % 2 == 1;
+            action /= 4;
+            let has_comment = action % 2 == 1;
+            action /= 2;
+            let lock = action % 2 == 0;
+
+            if has_comment {
+                let len = poll(2)? as usize;
+                let mut escaped = String::new();
+                for _ in 0..len.div_ceil(4) {
+                    let mut value = poll(5)?;
+                    for _ in 0..4 {
+                        let index = value % (COMMENT_TABLE.len() as u32 + 1);
+                        value /= COMMENT_TABLE.len() as u32 + 1;
+                        let c = *COMMENT_TABLE.get(index as usize).ok_or(FumenError::InvalidValue)?;
+                        escaped.push(c as char);
+                    }
+                }
+                escaped.truncate(len);
+                comment = unescape(&escaped);
+            }
+
+            let piece = decode_piece(kind, rotation, position)?;
+            let mut rows = arrayvec::ArrayVec::<[R; 40]>::new();
+            for colors in field.iter().take(FIELD_HEIGHT) {
+                let mut row = R::default();
+                for (x, &color) in colors.iter().enumerate() {
+                    row.set(x, color_cell(color));
+                }
+                rows.push(row);
+            }
+            while !rows.is_full() {
+                rows.push(R::default());
+            }
+            let mut board = Board::new();
+            board.set_field(rows);
+            let mut page = FumenPage::new(board);
+            page.piece = piece;
+            page.lock = lock;
+            page.set_quiz_comment(&comment);
+            pages.push(page);
+
+            if lock {
+                if let Some(piece) = piece {
+                    for (x, y) in piece.cells().iter().copied() {
+                        if !(0..10).contains(&x) || !(0..FIELD_HEIGHT as i32).contains(&y) {
+                            return Err(FumenError::InvalidValue);
+                        }
+                        field[y as usize][x as usize] = cell_color(piece.kind.cell());
+                    }
+                }
+                clear_lines(&mut field);
+                if rise {
+                    field.copy_within(0..FIELD_HEIGHT - 1, 1);
+                    field[0] = field[GARBAGE_ROW];
+                    field[GARBAGE_ROW] = [0; 10];
+                }
+                if mirror {
+                    for row in field.iter_mut().take(FIELD_HEIGHT) {
+                        row.reverse();
+                    }
+                }
+            }
+        }
+        Ok(Self { pages })
+    }
+}
+
+/// Run length encodes the difference between two fields. Returns whether they differ.
+fn encode_field(prev: &Field, field: &Field, values: &mut Vec<u32>) -> bool {
+    let diff = |i: usize| {
+        let y = FIELD_HEIGHT as i32 - 1 - (i / 10) as i32;
+        let row = if y < 0 { GARBAGE_ROW } else { y as usize };
+        field[row][i % 10] as u32 + 8 - prev[row][i % 10] as u32
+    };
+    let mut changed = false;
+    let mut start = 0;
+    while start < FIELD_CELLS {
+        let value = diff(start);
+        let mut end = start + 1;
+        while end < FIELD_CELLS && diff(end) == value {
+            end += 1;
+        }
+        changed |= value != 8;
+        push_values(values, value * FIELD_CELLS as u32 + (end - start - 1) as u32, 2);
+        start = end;
+    }
+    changed
+}
+
+fn push_values(values: &mut Vec<u32>, mut value: u32, count: u32) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+fn clear_lines(field: &mut Field) {
+    let mut rows: Vec<_> = field[..FIELD_HEIGHT].iter().copied().filter(|row| row.contains(&0)).collect();
+    rows.resize(FIELD_HEIGHT, [0; 10]);
+    field[..FIELD_HEIGHT].copy_from_slice(&rows);
+}
+
+fn cell_color(cell: CellType) -> u8 {
+    match cell {
+        CellType::Empty => 0,
+        CellType::I => 1,
+        CellType::L => 2,
+        CellType::O => 3,
+        CellType::Z => 4,
+        CellType::T => 5,
+        CellType::J => 6,
+        CellType::S => 7,
+        CellType::Garbage | CellType::Solid => 8
+    }
+}
+
+fn color_cell(color: u8) -> CellType {
+    match color {
+        1 => CellType::I,
+        2 => CellType::L,
+        3 => CellType::O,
+        4 => CellType::Z,
+        5 => CellType::T,
+        6 => CellType::J,
+        7 => CellType::S,
+        8 => CellType::Garbage,
+        _ => CellType::Empty
+    }
+}
+
+const PIECE_COLORS: [(PieceType, u32); 7] = [
+    (PieceType::I, 1),
+    (PieceType::L, 2),
+    (PieceType::O, 3),
+    (PieceType::Z, 4),
+    (PieceType::T, 5),
+    (PieceType::J, 6),
+    (PieceType::S, 7)
+];
+
+/// Fumen numbers rotations from upside down, clockwise.
+const ROTATIONS: [u32; 4] = [2, 1, 0, 3];
+
+/// Fumen positions pieces by their SRS center, except for a few states
+/// that are stored one cell off.
+fn position_offset(kind: PieceType, r: u8) -> (i32, i32) {
+    match (kind, r) {
+        (PieceType::O, 3) => (1, -1),
+        (PieceType::O, 2) => (1, 0),
+        (PieceType::O, 0) => (0, -1),
+        (PieceType::I, 2) => (1, 0),
+        (PieceType::I, 3) => (0, -1),
+        (PieceType::S, 0) => (0, -1),
+        (PieceType::S, 1) => (-1, 0),
+        (PieceType::Z, 0) => (0, -1),
+        (PieceType::Z, 3) => (1, 0),
+        _ => (0, 0)
+    }
+}
+
+fn encode_piece(piece: Piece) -> Result<(u32, u32, u32), FumenError> {
+    // Use the SRS state covering the same cells, so other rotation systems are shown correctly.
+    let srs = Piece::from_cells(piece.kind, piece.cells(), piece.r).unwrap();
+    let (x, y, r) = (srs.x, srs.y, srs.r);
+    let (offset_x, offset_y) = position_offset(piece.kind, r);
+    let (x, y) = (x - offset_x, y - offset_y);
+    if !(0..10).contains(&x) || !(0..FIELD_HEIGHT as i32).contains(&y) {
+        return Err(FumenError::PieceOutOfField);
+    }
+    let kind = PIECE_COLORS.iter().find(|&&(p, _)| p == piece.kind).unwrap().1;
+    let position = x as u32 + (FIELD_HEIGHT as i32 - 1 - y) as u32 * 10;
+    Ok((kind, ROTATIONS[r as usize], position))
+}
+
+fn decode_piece(kind: u32, rotation: u32, position: u32) -> Result<Option<Piece>, FumenError> {
+    let kind = match PIECE_COLORS.iter().find(|&&(_, c)| c == kind) {
+        Some(&(kind, _)) => kind,
+        None => return Ok(None)
+    };
+    let r = ROTATIONS.iter().position(|&c| c == rotation).unwrap() as u8;
+    let (offset_x, offset_y) = position_offset(kind, r);
+    let x = (position % 10) as i32 + offset_x;
+    let y = FIELD_HEIGHT as i32 - 1 - (position / 10) as i32 + offset_y;
+    Ok(Some(Piece {
+        kind,
+        x,
+        y,
+        r,
+        tspin: TspinType::None,
+        system: RotationSystem::Srs,
+        spin_rule: SpinRule::TSpin
+    }))
+}
+
+/// JavaScript's `escape`, which fumen runs comments through.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match std::char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit))
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        let (unit, len) = match (c, rest.get(1..2), rest.get(2..6), rest.get(1..3)) {
+            ('%', Some("u"), Some(digits), _) if hex(digits).is_some() => (hex(digits).unwrap(), 6),
+            ('%', _, _, Some(digits)) if hex(digits).is_some() => (hex(digits).unwrap(), 3),
+            _ => (c as u16, c.len_utf8())
+        };
+        units.push(unit);
+        rest = &rest[len..];
+    }
+    String::from_utf16_lossy(&units)
+}
@@ -1,6 +1,17 @@
 mod piece;
 mod board;
 mod queue;
+mod zobrist;
+mod attack;
+mod rotation;
+mod randomizer;
+mod fumen;
+mod ascii;
 pub use piece::*;
 pub use board::*;
 pub use queue::*;
+pub use attack::*;
+pub use rotation::*;
+pub use randomizer::*;
+pub use fumen::*;
+pub use ascii::*;
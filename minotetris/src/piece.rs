@@ -1,7 +1,10 @@
 use crate::*;
 use enumset::EnumSetType;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize };
 
 #[derive(PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CellType {
     Empty,
     Garbage,
@@ -16,6 +19,7 @@ pub enum CellType {
 }
 
 #[derive(EnumSetType, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PieceType {
     J,
     L,
@@ -27,14 +31,6 @@ pub enum PieceType {
 }
 
 impl PieceType {
-    pub fn offset_table(self, r: u8) -> &'static [(i32, i32)] {
-        let r = r as usize;
-        match self {
-            Self::O => &O_OFFSET_TABLE[r],
-            Self::I => &I_OFFSET_TABLE[r],
-            _ => &JLSTZ_OFFSET_TABLE[r]
-        }
-    }
     pub fn cells(self, r: u8) -> [(i32, i32); 4] {
         let r = r as usize;
         match self {
@@ -47,6 +43,30 @@ impl PieceType {
             Self::O => O_STATES[r]
         }
     }
+    /// The letter the piece is known by.
+    pub fn name(self) -> char {
+        match self {
+            Self::J => 'J',
+            Self::L => 'L',
+            Self::S => 'S',
+            Self::T => 'T',
+            Self::Z => 'Z',
+            Self::I => 'I',
+            Self::O => 'O'
+        }
+    }
+    pub fn from_name(name: char) -> Option<Self> {
+        Some(match name {
+            'J' => Self::J,
+            'L' => Self::L,
+            'S' => Self::S,
+            'T' => Self::T,
+            'Z' => Self::Z,
+            'I' => Self::I,
+            'O' => Self::O,
+            _ => return None
+        })
+    }
     pub fn cell(self) -> CellType {
         match self {
             Self::J => CellType::J,
@@ -60,7 +80,22 @@ impl PieceType {
     }
 }
 
+/// Which pieces can spin. T pieces always use the 3-corner rule.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpinRule {
+    /// Only T pieces spin.
+    #[default]
+    TSpin,
+    /// Other pieces that can't move after rotating get a mini spin.
+    AllMini,
+    /// Other pieces that can't move after rotating get a full spin.
+    AllSpin
+}
+
+/// Spin of the last rotation. Despite the name, any piece can spin under `SpinRule::AllMini` and `SpinRule::AllSpin`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TspinType {
     None,
     Mini,
@@ -68,34 +103,76 @@ pub enum TspinType {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Piece {
     pub kind: PieceType,
     pub x: i32,
     pub y: i32,
     pub r: u8,
-    pub tspin: TspinType
+    pub tspin: TspinType,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub system: RotationSystem,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spin_rule: SpinRule
 }
 
 impl Piece {
-    pub fn spawn(board: &Board<impl Row>, kind: PieceType) -> Self {
+    pub fn spawn(board: &Board<impl Row>, kind: PieceType, system: RotationSystem, spin_rule: SpinRule) -> Self {
+        let (x, y) = system.spawn_position(kind);
         let mut piece = Self {
             kind,
-            x: 4,
-            y: 20,
+            x,
+            y,
             r: 0,
-            tspin: TspinType::None
+            tspin: TspinType::None,
+            system,
+            spin_rule
         };
         piece.soft_drop(board);
         piece
     }
+    /// The SRS piece covering `cells`, trying rotation state `r` first since
+    /// I, S, Z and O pieces cover the same cells in more than one state.
+    pub fn from_cells(kind: PieceType, cells: [(i32, i32); 4], r: u8) -> Option<Self> {
+        let mut cells = cells;
+        cells.sort_unstable();
+        (0..4).map(|i| (r + i) % 4).find_map(|r| {
+            let mut srs = kind.cells(r);
+            srs.sort_unstable();
+            let (x, y) = (cells[0].0 - srs[0].0, cells[0].1 - srs[0].1);
+            let same = srs.iter().zip(&cells).all(|(&(srs_x, srs_y), &cell)| (srs_x + x, srs_y + y) == cell);
+            if same {
+                Some(Self {
+                    kind,
+                    x,
+                    y,
+                    r,
+                    tspin: TspinType::None,
+                    system: RotationSystem::Srs,
+                    spin_rule: SpinRule::TSpin
+                })
+            } else {
+                None
+            }
+        })
+    }
     pub fn cells(&self) -> [(i32, i32); 4] {
-        let mut cells = self.kind.cells(self.r);
+        let mut cells = self.system.cells(self.kind, self.r);
         for (x, y) in &mut cells {
             *x += self.x;
             *y += self.y;
         }
         cells
     }
+    /// Whether both pieces are the same kind and spin and cover the same cells,
+    /// whatever rotation system or state got them there.
+    pub fn same_placement(&self, other: &Piece) -> bool {
+        let mut cells = self.cells();
+        let mut other_cells = other.cells();
+        cells.sort_unstable();
+        other_cells.sort_unstable();
+        self.kind == other.kind && self.tspin == other.tspin && cells == other_cells
+    }
     pub fn move_left(&mut self, board: &Board<impl Row>) -> bool {
         self.try_move(board, self.x - 1, self.y, self.r)
     }
@@ -129,59 +206,95 @@ impl Piece {
     pub fn turn_right(&mut self, board: &Board<impl Row>) -> bool {
         self.rotate(board,if self.r < 3 { self.r + 1 } else { 0 })
     }
+    pub fn turn_180(&mut self, board: &Board<impl Row>, kicks: Kicks180) -> bool {
+        let r = (self.r + 2) % 4;
+        let (base_x, base_y) = self.system.rotation_offset(self.kind, self.r, r);
+        for &(kick_x, kick_y) in kicks.table(self.r) {
+            let x = self.x + base_x + kick_x;
+            let y = self.y + base_y + kick_y;
+            if self.try_move(board, x, y, r) {
+                self.check_spin(board, false);
+                return true;
+            }
+        }
+        false
+    }
     fn rotate(&mut self, board: &Board<impl Row>, r: u8) -> bool {
-        let from_table = self.kind.offset_table(self.r);
-        let to_table = self.kind.offset_table(r);
-        for (i, (from, to)) in from_table.iter().zip(to_table.iter()).enumerate() {
-            let x = self.x + from.0 - to.0;
-            let y = self.y + from.1 - to.1;
+        for (i, &(kick_x, kick_y)) in self.system.kicks(self.kind, self.r, r).iter().enumerate() {
+            let x = self.x + kick_x;
+            let y = self.y + kick_y;
             if self.try_move(board, x, y, r) {
-                if self.kind == PieceType::T {
-                    const CORNER_CELLS: [(i32, i32); 4] = [
-                        (-1, -1),
-                        (-1, 1),
-                        (1, 1),
-                        (1, -1)
-                    ];
-                    let mut corners = 0;
-                    for &(corner_x, corner_y) in &CORNER_CELLS {
-                        if board.occupied(x + corner_x, y + corner_y) {
-                            corners += 1;
-                        }
-                    }
-                    if corners > 2 {
-                        let front_corner_cells = match r {
-                            0 => [(-1, 1), (1, 1)],
-                            1 => [(1, 1), (1, -1)],
-                            2 => [(-1, -1), (1, -1)],
-                            3 => [(-1, -1), (-1, 1)],
-                            _ => unreachable!()
-                        };
-                        let mut front_corners = 0;
-                        for &(corner_x, corner_y) in &front_corner_cells {
-                            if board.occupied(x + corner_x, y + corner_y) {
-                                front_corners += 1;
-                            }
-                        }
-                        self.tspin = if front_corners >= 2 || i == 4 {
-                            TspinType::Full
-                        } else {
-                            TspinType::Mini
-                        };
-                    }
-                }
+                self.check_spin(board, self.system.full_tspin_kick(i));
                 return true;
             }
+            if i == 0 && self.system.center_column_blocked(board, Piece { x, y, r, ..*self }) {
+                return false;
+            }
         }
         false
     }
+    /// Marks a piece that was just rotated into place as a spin.
+    /// `last_kick` upgrades a T-spin mini to a full T-spin.
+    fn check_spin(&mut self, board: &Board<impl Row>, last_kick: bool) {
+        if self.kind != PieceType::T {
+            if self.spin_rule == SpinRule::TSpin {
+                return;
+            }
+            let immobile = !self.clone().move_left(board)
+                && !self.clone().move_right(board)
+                && !self.clone().soft_drop(board)
+                && !self.clone().try_move(board, self.x, self.y + 1, self.r);
+            if immobile {
+                self.tspin = match self.spin_rule {
+                    SpinRule::AllMini => TspinType::Mini,
+                    _ => TspinType::Full
+                };
+            }
+            return;
+        }
+        const CORNER_CELLS: [(i32, i32); 4] = [
+            (-1, -1),
+            (-1, 1),
+            (1, 1),
+            (1, -1)
+        ];
+        let (x, y) = (self.x, self.y);
+        let mut corners = 0;
+        for &(corner_x, corner_y) in &CORNER_CELLS {
+            if board.occupied(x + corner_x, y + corner_y) {
+                corners += 1;
+            }
+        }
+        if corners > 2 {
+            let front_corner_cells = match self.system.facing(self.r) {
+                0 => [(-1, 1), (1, 1)],
+                1 => [(1, 1), (1, -1)],
+                2 => [(-1, -1), (1, -1)],
+                3 => [(-1, -1), (-1, 1)],
+                _ => unreachable!()
+            };
+            let mut front_corners = 0;
+            for &(corner_x, corner_y) in &front_corner_cells {
+                if board.occupied(x + corner_x, y + corner_y) {
+                    front_corners += 1;
+                }
+            }
+            self.tspin = if front_corners >= 2 || last_kick {
+                TspinType::Full
+            } else {
+                TspinType::Mini
+            };
+        }
+    }
     fn try_move(&mut self, board: &Board<impl Row>, x: i32, y: i32, r: u8) -> bool {
         let new = Piece {
             kind: self.kind,
             x,
             y,
             r,
-            tspin: TspinType::None
+            tspin: TspinType::None,
+            system: self.system,
+            spin_rule: self.spin_rule
         };
         if board.piece_fits(new) {
             *self = new;
@@ -192,74 +305,6 @@ impl Piece {
     }
 }
 
-const JLSTZ_OFFSET_TABLE: [[(i32, i32); 5]; 4] = [
-    [
-        (0, 0),
-        (0, 0),
-        (0, 0),
-        (0, 0),
-        (0, 0)
-    ], [
-        (0, 0),
-        (1, 0),
-        (1, -1),
-        (0, 2),
-        (1, 2)
-    ], [
-        (0, 0),
-        (0, 0),
-        (0, 0),
-        (0, 0),
-        (0, 0)
-    ], [
-        (0, 0),
-        (-1, 0),
-        (-1, -1),
-        (0, 2),
-        (-1, 2)
-    ]
-];
-
-const O_OFFSET_TABLE: [[(i32, i32); 1]; 4] = [
-    [
-        (0, 0)
-    ], [
-        (0, -1)
-    ], [
-        (-1, -1)
-    ], [
-        (-1, 0)
-    ]
-];
-
-const I_OFFSET_TABLE: [[(i32, i32); 5]; 4] = [
-    [
-        (0, 0),
-        (-1, 0),
-        (2, 0),
-        (-1, 0),
-        (2, 0)
-    ], [
-        (-1, 0),
-        (0, 0),
-        (0, 0),
-        (0, 1),
-        (0, -2)
-    ], [
-        (-1, 1),
-        (1, 1),
-        (-2, 1),
-        (1, 0),
-        (-2, 0)
-    ], [
-        (0, 1),
-        (0, 1),
-        (0, 1),
-        (0, -1),
-        (0, 2)
-    ]
-];
-
 const J_STATES: [[(i32, i32); 4]; 4] = [
     [(-1, 1), (-1, 0), (0, 0), (1, 0)],
     [(0, 1), (0, 0), (0, -1), (1, 1)],
@@ -1,46 +1,75 @@
 use std::collections::VecDeque;
 
 use crate::*;
-use enumset::EnumSet;
-use arrayvec::ArrayVec;
 use rand::prelude::*;
+use enumset::EnumSet;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize, Serializer, Deserializer, ser::Error };
 
 pub struct PieceQueue {
     queue: VecDeque<PieceType>,
-    bag: ArrayVec<[PieceType; 7]>,
-}
-
-fn random_bag(rng: &mut (impl Rng + ?Sized)) -> ArrayVec<[PieceType; 7]> {
-    let mut bag: ArrayVec<[PieceType; 7]> = EnumSet::all().into_iter().collect();
-    bag.shuffle(rng);
-    bag
+    randomizer: Box<dyn Randomizer>
 }
 
 impl PieceQueue {
-    pub fn new(len: usize, rng: &mut (impl Rng + ?Sized)) -> Self {
+    pub fn new(len: usize, randomizer: Box<dyn Randomizer>, rng: &mut (impl Rng + ?Sized)) -> Self {
         let mut queue = Self {
             queue: VecDeque::with_capacity(len + 1),
-            bag: random_bag(rng)
+            randomizer
         };
         for _ in 0..len {
-            queue.queue_from_bag(rng);
+            queue.queue_next(rng);
         }
         queue
     }
 
     pub fn next(&mut self, rng: &mut (impl Rng + ?Sized)) -> PieceType {
-        self.queue_from_bag(rng);
+        self.queue_next(rng);
         self.queue.pop_front().unwrap()
     }
 
-    fn queue_from_bag(&mut self, rng: &mut (impl Rng + ?Sized)) {
-        if self.bag.is_empty() {
-            self.bag = random_bag(rng);
-        }
-        self.queue.push_back(self.bag.pop().unwrap());
+    fn queue_next(&mut self, mut rng: &mut (impl Rng + ?Sized)) {
+        let piece = self.randomizer.next(&mut rng);
+        self.queue.push_back(piece);
     }
 
     pub fn get_queue(&self) -> &VecDeque<PieceType> {
         &self.queue
     }
+
+    /// Pieces left in the current 7-bag after the last piece in the queue,
+    /// if the randomizer deals out 7-bags.
+    pub fn bag_state(&self) -> Option<EnumSet<PieceType>> {
+        self.randomizer.bag_state()
+    }
+}
+
+/// How queues are serialized. Only queues using a built in randomizer can be serialized.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "PieceQueue")]
+struct QueueData {
+    queue: VecDeque<PieceType>,
+    randomizer: SavedRandomizer
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for PieceQueue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        QueueData {
+            queue: self.queue.clone(),
+            randomizer: self.randomizer.save().ok_or_else(|| S::Error::custom("the randomizer can't be saved"))?
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for PieceQueue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = QueueData::deserialize(deserializer)?;
+        Ok(Self {
+            queue: data.queue,
+            randomizer: data.randomizer.load()
+        })
+    }
 }
@@ -0,0 +1,319 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use rand::prelude::*;
+use enumset::EnumSet;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize };
+
+use crate::*;
+
+const PIECES: [PieceType; 7] = [
+    PieceType::J,
+    PieceType::L,
+    PieceType::S,
+    PieceType::T,
+    PieceType::Z,
+    PieceType::I,
+    PieceType::O
+];
+
+/// Decides which piece comes next.
+pub trait Randomizer: Send {
+    fn next(&mut self, rng: &mut dyn RngCore) -> PieceType;
+    /// A copy of the randomizer and its state, if it is one that can be saved.
+    fn save(&self) -> Option<SavedRandomizer> {
+        None
+    }
+    /// Pieces left in the current bag, if this randomizer deals out 7-bags.
+    fn bag_state(&self) -> Option<EnumSet<PieceType>> {
+        None
+    }
+}
+
+/// Why a randomizer can't be built or loaded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RandomizerError {
+    NoCopies,
+    EmptySequence,
+    IndexOutOfRange
+}
+
+impl fmt::Display for RandomizerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoCopies => write!(f, "bags need at least one copy of every piece"),
+            Self::EmptySequence => write!(f, "piece sequence is empty"),
+            Self::IndexOutOfRange => write!(f, "sequence index is past the end of the sequence")
+        }
+    }
+}
+
+impl std::error::Error for RandomizerError {}
+
+/// Deals out shuffled bags holding `copies` of every piece.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "BagData", into = "BagData"))]
+pub struct BagRandomizer {
+    copies: usize,
+    bag: Vec<PieceType>
+}
+
+/// How `BagRandomizer`s are serialized, checked before loading.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize, Clone)]
+struct BagData {
+    copies: usize,
+    bag: Vec<PieceType>
+}
+
+impl BagRandomizer {
+    pub fn new(copies: usize) -> Result<Self, RandomizerError> {
+        if copies == 0 {
+            return Err(RandomizerError::NoCopies);
+        }
+        Ok(Self {
+            copies,
+            bag: Vec::with_capacity(copies * PIECES.len())
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BagData> for BagRandomizer {
+    type Error = RandomizerError;
+    fn try_from(data: BagData) -> Result<Self, RandomizerError> {
+        let mut randomizer = Self::new(data.copies)?;
+        randomizer.bag = data.bag;
+        Ok(randomizer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<BagRandomizer> for BagData {
+    fn from(randomizer: BagRandomizer) -> Self {
+        Self {
+            copies: randomizer.copies,
+            bag: randomizer.bag
+        }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next(&mut self, rng: &mut dyn RngCore) -> PieceType {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend_from_slice(&PIECES);
+            }
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap()
+    }
+    fn save(&self) -> Option<SavedRandomizer> {
+        Some(SavedRandomizer::Bag(self.clone()))
+    }
+    fn bag_state(&self) -> Option<EnumSet<PieceType>> {
+        if self.copies == 1 {
+            Some(self.bag.iter().copied().collect())
+        } else {
+            None
+        }
+    }
+}
+
+/// Every piece is equally likely every time.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UniformRandomizer;
+
+impl Randomizer for UniformRandomizer {
+    fn next(&mut self, rng: &mut dyn RngCore) -> PieceType {
+        *PIECES.choose(rng).unwrap()
+    }
+    fn save(&self) -> Option<SavedRandomizer> {
+        Some(SavedRandomizer::Uniform(self.clone()))
+    }
+}
+
+/// TGM style: rerolls up to `rolls` times to avoid the last 4 pieces.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HistoryRandomizer {
+    history: [PieceType; 4],
+    rolls: u32,
+    first: bool
+}
+
+impl HistoryRandomizer {
+    pub fn new(rolls: u32) -> Self {
+        Self {
+            history: [PieceType::Z, PieceType::S, PieceType::S, PieceType::Z],
+            rolls,
+            first: true
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next(&mut self, rng: &mut dyn RngCore) -> PieceType {
+        let piece = if self.first {
+            // The first piece is never one that forces an overhang.
+            self.first = false;
+            *[PieceType::J, PieceType::L, PieceType::T, PieceType::I].choose(rng).unwrap()
+        } else {
+            let mut piece = *PIECES.choose(rng).unwrap();
+            for _ in 1..self.rolls {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = *PIECES.choose(rng).unwrap();
+            }
+            piece
+        };
+        self.history.rotate_left(1);
+        self.history[3] = piece;
+        piece
+    }
+    fn save(&self) -> Option<SavedRandomizer> {
+        Some(SavedRandomizer::History(self.clone()))
+    }
+}
+
+/// A list of pieces that isn't empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Vec<PieceType>", into = "Vec<PieceType>")
+)]
+pub struct PieceSequence(Vec<PieceType>);
+
+impl PieceSequence {
+    pub fn pieces(&self) -> &[PieceType] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<PieceType>> for PieceSequence {
+    type Error = RandomizerError;
+    fn try_from(pieces: Vec<PieceType>) -> Result<Self, RandomizerError> {
+        if pieces.is_empty() {
+            Err(RandomizerError::EmptySequence)
+        } else {
+            Ok(Self(pieces))
+        }
+    }
+}
+
+impl From<PieceSequence> for Vec<PieceType> {
+    fn from(sequence: PieceSequence) -> Self {
+        sequence.0
+    }
+}
+
+/// Repeats a fixed list of pieces.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "SequenceData", into = "SequenceData"))]
+pub struct SequenceRandomizer {
+    pieces: PieceSequence,
+    index: usize
+}
+
+/// How `SequenceRandomizer`s are serialized, checked before loading.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize, Clone)]
+struct SequenceData {
+    pieces: PieceSequence,
+    index: usize
+}
+
+impl SequenceRandomizer {
+    pub fn new(pieces: PieceSequence) -> Self {
+        Self {
+            pieces,
+            index: 0
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SequenceData> for SequenceRandomizer {
+    type Error = RandomizerError;
+    fn try_from(data: SequenceData) -> Result<Self, RandomizerError> {
+        if data.index >= data.pieces.0.len() {
+            return Err(RandomizerError::IndexOutOfRange);
+        }
+        Ok(Self {
+            pieces: data.pieces,
+            index: data.index
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SequenceRandomizer> for SequenceData {
+    fn from(randomizer: SequenceRandomizer) -> Self {
+        Self {
+            pieces: randomizer.pieces,
+            index: randomizer.index
+        }
+    }
+}
+
+impl Randomizer for SequenceRandomizer {
+    fn next(&mut self, _rng: &mut dyn RngCore) -> PieceType {
+        let pieces = &self.pieces.0;
+        let piece = pieces[self.index];
+        self.index = (self.index + 1) % pieces.len();
+        piece
+    }
+    fn save(&self) -> Option<SavedRandomizer> {
+        Some(SavedRandomizer::Sequence(self.clone()))
+    }
+}
+
+/// One of the built in randomizers in the middle of dealing pieces, for saving queues.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SavedRandomizer {
+    Bag(BagRandomizer),
+    Uniform(UniformRandomizer),
+    History(HistoryRandomizer),
+    Sequence(SequenceRandomizer)
+}
+
+impl SavedRandomizer {
+    pub fn load(self) -> Box<dyn Randomizer> {
+        match self {
+            Self::Bag(randomizer) => Box::new(randomizer),
+            Self::Uniform(randomizer) => Box::new(randomizer),
+            Self::History(randomizer) => Box::new(randomizer),
+            Self::Sequence(randomizer) => Box::new(randomizer)
+        }
+    }
+}
+
+/// Picks one of the built in randomizers, for use in option files.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RandomizerKind {
+    #[default]
+    Bag7,
+    Bag14,
+    Uniform,
+    /// 4 piece history with 6 rolls, as in TGM2.
+    Tgm,
+    Sequence(PieceSequence)
+}
+
+impl RandomizerKind {
+    pub fn build(&self) -> Box<dyn Randomizer> {
+        match self {
+            Self::Bag7 => Box::new(BagRandomizer::new(1).unwrap()),
+            Self::Bag14 => Box::new(BagRandomizer::new(2).unwrap()),
+            Self::Uniform => Box::new(UniformRandomizer),
+            Self::Tgm => Box::new(HistoryRandomizer::new(6)),
+            Self::Sequence(pieces) => Box::new(SequenceRandomizer::new(pieces.clone()))
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use arrayvec::ArrayVec;
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Deserialize };
+
+use crate::*;
+
+/// How pieces are shaped, where they spawn and how they kick when rotated.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RotationSystem {
+    /// Guideline SRS.
+    #[default]
+    Srs,
+    /// SRS with I kicks that favour the same side in both directions, as in TETR.IO.
+    SrsPlus,
+    /// The TGM series' rotation system. Pieces spawn flat side up and only kick sideways.
+    Ars,
+    /// SRS shapes without any kicks.
+    Classic
+}
+
+pub type Kicks = ArrayVec<[(i32, i32); 5]>;
+
+impl RotationSystem {
+    pub fn cells(self, kind: PieceType, r: u8) -> [(i32, i32); 4] {
+        match self {
+            Self::Ars => {
+                let r = r as usize;
+                match kind {
+                    PieceType::J => ARS_J_STATES[r],
+                    PieceType::L => ARS_L_STATES[r],
+                    PieceType::S => ARS_S_STATES[r % 2],
+                    PieceType::T => ARS_T_STATES[r],
+                    PieceType::Z => ARS_Z_STATES[r % 2],
+                    PieceType::I => ARS_I_STATES[r % 2],
+                    PieceType::O => ARS_O_STATE
+                }
+            }
+            _ => kind.cells(r)
+        }
+    }
+
+    /// Where a piece is placed before it is dropped into view.
+    pub fn spawn_position(self, _kind: PieceType) -> (i32, i32) {
+        match self {
+            // ARS pieces hang a row lower from their center, so start them a row higher.
+            Self::Ars => (4, 21),
+            _ => (4, 20)
+        }
+    }
+
+    /// Offset that turns the piece in place, before any kicks.
+    pub fn rotation_offset(self, kind: PieceType, from: u8, to: u8) -> (i32, i32) {
+        match self {
+            Self::Ars => (0, 0),
+            _ => {
+                let from = srs_offset_table(kind, from)[0];
+                let to = srs_offset_table(kind, to)[0];
+                (from.0 - to.0, from.1 - to.1)
+            }
+        }
+    }
+
+    /// Offsets to try in order when turning a piece from `from` to `to` a quarter turn away.
+    pub fn kicks(self, kind: PieceType, from: u8, to: u8) -> Kicks {
+        match self {
+            Self::Srs => Self::srs_kicks(kind, from, to),
+            Self::SrsPlus if kind == PieceType::I => {
+                let (base_x, base_y) = self.rotation_offset(kind, from, to);
+                let clockwise = (from + 1) % 4 == to;
+                SRS_PLUS_I_KICKS[from as usize][if clockwise { 0 } else { 1 }]
+                    .iter()
+                    .map(|&(x, y)| (base_x + x, base_y + y))
+                    .collect()
+            }
+            Self::SrsPlus => Self::srs_kicks(kind, from, to),
+            Self::Ars => match kind {
+                PieceType::I | PieceType::O => [(0, 0)].iter().copied().collect(),
+                _ => [(0, 0), (1, 0), (-1, 0)].iter().copied().collect()
+            },
+            Self::Classic => [self.rotation_offset(kind, from, to)].iter().copied().collect()
+        }
+    }
+
+    fn srs_kicks(kind: PieceType, from: u8, to: u8) -> Kicks {
+        srs_offset_table(kind, from)
+            .iter()
+            .zip(srs_offset_table(kind, to).iter())
+            .map(|(from, to)| (from.0 - to.0, from.1 - to.1))
+            .collect()
+    }
+
+    /// Whether a T piece that needed the kick at `index` always counts as a full T-spin.
+    pub fn full_tspin_kick(self, index: usize) -> bool {
+        match self {
+            Self::Srs | Self::SrsPlus => index == 4,
+            _ => false
+        }
+    }
+
+    /// The SRS state a T piece in state `r` points the same way as, for finding its front corners.
+    pub fn facing(self, r: u8) -> u8 {
+        match self {
+            Self::Ars => (r + 2) % 4,
+            _ => r
+        }
+    }
+
+    /// ARS won't kick J, L and T pieces when the first blocked cell, reading the 3x3 box
+    /// from the top left, is in the center column.
+    pub fn center_column_blocked(self, board: &Board<impl Row>, piece: Piece) -> bool {
+        if self != Self::Ars || !matches!(piece.kind, PieceType::J | PieceType::L | PieceType::T) {
+            return false;
+        }
+        let cells = piece.cells();
+        for y in (-1..=1).rev() {
+            for x in -1..=1 {
+                if cells.contains(&(piece.x + x, piece.y + y)) && board.occupied(piece.x + x, piece.y + y) {
+                    return x == 0;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Kick table used by 180 rotations.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Kicks180 {
+    /// TETR.IO's SRS+ table.
+    SrsPlus,
+    /// Jstris style, which only rotates in place.
+    Jstris
+}
+
+impl Kicks180 {
+    pub fn table(self, r: u8) -> &'static [(i32, i32)] {
+        match self {
+            Self::SrsPlus => &SRS_PLUS_180_KICKS[r as usize],
+            Self::Jstris => &[(0, 0)]
+        }
+    }
+}
+
+fn srs_offset_table(kind: PieceType, r: u8) -> &'static [(i32, i32)] {
+    let r = r as usize;
+    match kind {
+        PieceType::O => &O_OFFSET_TABLE[r],
+        PieceType::I => &I_OFFSET_TABLE[r],
+        _ => &JLSTZ_OFFSET_TABLE[r]
+    }
+}
+
+/// Extra offsets tried after rotating 180 degrees from each state, in order.
+const SRS_PLUS_180_KICKS: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)]
+];
+
+/// SRS+ I kicks from each state, turning clockwise then counterclockwise,
+/// on top of the in-place rotation.
+const SRS_PLUS_I_KICKS: [[[(i32, i32); 5]; 2]; 4] = [
+    [
+        [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
+        [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)]
+    ], [
+        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]
+    ], [
+        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]
+    ], [
+        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)]
+    ]
+];
+
+const JLSTZ_OFFSET_TABLE: [[(i32, i32); 5]; 4] = [
+    [
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0)
+    ], [
+        (0, 0),
+        (1, 0),
+        (1, -1),
+        (0, 2),
+        (1, 2)
+    ], [
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0),
+        (0, 0)
+    ], [
+        (0, 0),
+        (-1, 0),
+        (-1, -1),
+        (0, 2),
+        (-1, 2)
+    ]
+];
+
+const O_OFFSET_TABLE: [[(i32, i32); 1]; 4] = [
+    [
+        (0, 0)
+    ], [
+        (0, -1)
+    ], [
+        (-1, -1)
+    ], [
+        (-1, 0)
+    ]
+];
+
+const I_OFFSET_TABLE: [[(i32, i32); 5]; 4] = [
+    [
+        (0, 0),
+        (-1, 0),
+        (2, 0),
+        (-1, 0),
+        (2, 0)
+    ], [
+        (-1, 0),
+        (0, 0),
+        (0, 0),
+        (0, 1),
+        (0, -2)
+    ], [
+        (-1, 1),
+        (1, 1),
+        (-2, 1),
+        (1, 0),
+        (-2, 0)
+    ], [
+        (0, 1),
+        (0, 1),
+        (0, 1),
+        (0, -1),
+        (0, 2)
+    ]
+];
+
+const ARS_J_STATES: [[(i32, i32); 4]; 4] = [
+    [(-1, 0), (0, 0), (1, 0), (1, -1)],
+    [(-1, -1), (0, 1), (0, 0), (0, -1)],
+    [(-1, 0), (-1, -1), (0, -1), (1, -1)],
+    [(0, 1), (0, 0), (0, -1), (1, 1)],
+];
+
+const ARS_L_STATES: [[(i32, i32); 4]; 4] = [
+    [(-1, 0), (-1, -1), (0, 0), (1, 0)],
+    [(-1, 1), (0, 1), (0, 0), (0, -1)],
+    [(-1, -1), (0, -1), (1, 0), (1, -1)],
+    [(0, 1), (0, 0), (0, -1), (1, -1)],
+];
+
+const ARS_S_STATES: [[(i32, i32); 4]; 2] = [
+    [(-1, -1), (0, 0), (0, -1), (1, 0)],
+    [(-1, 1), (-1, 0), (0, 0), (0, -1)],
+];
+
+const ARS_T_STATES: [[(i32, i32); 4]; 4] = [
+    [(-1, 0), (0, 0), (0, -1), (1, 0)],
+    [(-1, 0), (0, 1), (0, 0), (0, -1)],
+    [(-1, -1), (0, 0), (0, -1), (1, -1)],
+    [(0, 1), (0, 0), (0, -1), (1, 0)],
+];
+
+const ARS_Z_STATES: [[(i32, i32); 4]; 2] = [
+    [(-1, 0), (0, 0), (0, -1), (1, -1)],
+    [(0, 0), (0, -1), (1, 1), (1, 0)],
+];
+
+const ARS_I_STATES: [[(i32, i32); 4]; 2] = [
+    [(-1, 0), (0, 0), (1, 0), (2, 0)],
+    [(1, 1), (1, 0), (1, -1), (1, -2)],
+];
+
+const ARS_O_STATE: [(i32, i32); 4] = [(0, 0), (0, -1), (1, 0), (1, -1)];
@@ -0,0 +1,58 @@
+use crate::*;
+
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn cell_keys() -> [[u64; 10]; 40] {
+    let mut keys = [[0; 10]; 40];
+    let mut y = 0;
+    while y < 40 {
+        let mut x = 0;
+        while x < 10 {
+            keys[y][x] = splitmix64((y * 10 + x) as u64);
+            x += 1;
+        }
+        y += 1;
+    }
+    keys
+}
+
+const fn hold_keys() -> [u64; 7] {
+    let mut keys = [0; 7];
+    let mut i = 0;
+    while i < 7 {
+        keys[i] = splitmix64(400 + i as u64);
+        i += 1;
+    }
+    keys
+}
+
+const CELL_KEYS: [[u64; 10]; 40] = cell_keys();
+const HOLD_KEYS: [u64; 7] = hold_keys();
+const B2B_KEY: u64 = splitmix64(407);
+const COMBO_SEED: u64 = 408;
+
+impl<R: Row> Board<R> {
+    pub fn zobrist_hash(&self) -> u64 {
+        let max_height = self.column_heights().iter().copied().max().unwrap() as usize;
+        let mut hash = 0;
+        for (row, keys) in self.rows().iter().zip(CELL_KEYS.iter()).take(max_height) {
+            for (x, &key) in keys.iter().enumerate() {
+                if row.get(x) {
+                    hash ^= key;
+                }
+            }
+        }
+        if let Some(hold) = self.hold {
+            hash ^= HOLD_KEYS[hold as usize];
+        }
+        if self.b2b {
+            hash ^= B2B_KEY;
+        }
+        hash ^ splitmix64(COMBO_SEED ^ ((self.combo as u64) << 32))
+    }
+}
@@ -0,0 +1,39 @@
+use minotetris::*;
+
+#[test]
+fn position_round_trip() {
+    let text = "\
+hold: T
+combo: 1
+b2b: true
+....ttt...
+##L..t.###
+###OO.####";
+    let position: Position = text.parse().unwrap();
+    assert_eq!(position.board.hold, Some(PieceType::T));
+    assert_eq!(position.board.combo, 1);
+    assert!(position.board.b2b);
+    assert_eq!(position.board.column_heights(), &[2, 2, 2, 1, 1, 0, 1, 2, 2, 2]);
+    let piece = position.piece.unwrap();
+    assert_eq!((piece.kind, piece.x, piece.y, piece.r), (PieceType::T, 5, 2, 2));
+    assert_eq!(position.to_string(), text);
+}
+
+#[test]
+fn headers_are_optional() {
+    let board: Board = "
+        #.........
+        ##.#######
+    ".parse().unwrap();
+    assert_eq!(board.hold, None);
+    assert_eq!(board.column_heights(), &[2, 1, 0, 1, 1, 1, 1, 1, 1, 1]);
+    assert_eq!(board.to_string(), "hold: -\ncombo: 0\nb2b: false\n#.........\n##.#######");
+}
+
+#[test]
+fn invalid_positions() {
+    assert!("hold: X".parse::<Board>().is_err());
+    assert!("..........\n.........".parse::<Board>().is_err());
+    assert!("tt........".parse::<Position>().is_err());
+    assert!("..t.......\n.ttt......".parse::<Board>().is_err());
+}
@@ -0,0 +1,126 @@
+use minotetris::*;
+
+const PIECES: [PieceType; 7] = [
+    PieceType::J,
+    PieceType::L,
+    PieceType::S,
+    PieceType::T,
+    PieceType::Z,
+    PieceType::I,
+    PieceType::O
+];
+
+fn piece(kind: PieceType, x: i32, y: i32, r: u8) -> Piece {
+    Piece {
+        kind,
+        x,
+        y,
+        r,
+        tspin: TspinType::None,
+        system: RotationSystem::Srs,
+        spin_rule: SpinRule::TSpin
+    }
+}
+
+#[test]
+fn empty_field() {
+    let fumen = Fumen::<u16>::decode("v115@vhAAgH").unwrap();
+    assert_eq!(fumen.pages.len(), 1);
+    assert!(fumen.pages[0].piece.is_none());
+    assert_eq!(fumen.encode().unwrap(), "v115@vhAAgH");
+}
+
+#[test]
+fn pieces_round_trip() {
+    for &kind in &PIECES {
+        for r in 0..4 {
+            for &(x, y) in &[(1, 2), (4, 10), (8, 20)] {
+                let piece = piece(kind, x, y, r);
+                if !Board::<u16>::new().piece_fits(piece) {
+                    continue;
+                }
+                let mut page = FumenPage::new(Board::<u16>::new());
+                page.piece = Some(piece);
+                let fumen = Fumen { pages: vec![page] }.encode().unwrap();
+                let decoded = Fumen::<u16>::decode(&fumen).unwrap();
+                assert_eq!(decoded.pages[0].piece, Some(piece), "{}", fumen);
+            }
+        }
+    }
+}
+
+#[test]
+fn sequence_round_trip() {
+    let pieces = vec![
+        piece(PieceType::I, 1, 0, 0),
+        piece(PieceType::O, 4, 0, 0),
+        piece(PieceType::L, 7, 0, 0),
+        piece(PieceType::J, 1, 1, 0),
+        piece(PieceType::I, 9, 2, 1)
+    ];
+    let mut fumen = Fumen::sequence(&Board::<ColoredRow>::new(), pieces);
+    fumen.pages[0].comment = "hello, wörld".to_owned();
+    fumen.pages[0].queue = vec![PieceType::I, PieceType::O, PieceType::L];
+    fumen.pages[2].board.hold = Some(PieceType::S);
+    let encoded = fumen.encode().unwrap();
+
+    let decoded = Fumen::<ColoredRow>::decode(&encoded).unwrap();
+    assert_eq!(decoded.pages.len(), fumen.pages.len());
+    for (page, decoded) in fumen.pages.iter().zip(&decoded.pages) {
+        assert_eq!(page.board.compress().rows(), decoded.board.compress().rows());
+        assert_eq!(page.board.hold, decoded.board.hold);
+        assert_eq!(page.piece, decoded.piece);
+        assert_eq!(page.queue, decoded.queue);
+        assert_eq!(page.comment, decoded.comment);
+    }
+    assert_eq!(decoded.encode().unwrap(), encoded);
+}
+
+#[test]
+fn piece_above_field_is_an_error() {
+    let mut page = FumenPage::new(Board::<u16>::new());
+    page.piece = Some(piece(PieceType::T, 4, 23, 0));
+    assert_eq!(Fumen { pages: vec![page] }.encode(), Err(FumenError::PieceOutOfField));
+}
+
+#[test]
+fn decodes_every_rotation() {
+    // T, I, O, S and Z in the spawn, right, reverse and left states in turn, all stored
+    // at column 4, row 10. Encoded by hand from the format rather than with `encode`.
+    // Fumen shows the two states of a symmetric piece on the same cells.
+    let fumen = Fumen::<u16>::decode(
+        "v115@vhTVemN+eF+ed+eR+eJ+eB+eZ+eT+eL+eD+eb+eX+e?P+eH+ef+eU+eM+eE+ec+e"
+    ).unwrap();
+    let expected: [[(i32, i32); 4]; 20] = [
+        [(3, 10), (4, 10), (4, 11), (5, 10)],
+        [(4, 9), (4, 10), (4, 11), (5, 10)],
+        [(3, 10), (4, 9), (4, 10), (5, 10)],
+        [(3, 10), (4, 9), (4, 10), (4, 11)],
+        [(3, 10), (4, 10), (5, 10), (6, 10)],
+        [(4, 8), (4, 9), (4, 10), (4, 11)],
+        [(3, 10), (4, 10), (5, 10), (6, 10)],
+        [(4, 8), (4, 9), (4, 10), (4, 11)],
+        [(4, 9), (4, 10), (5, 9), (5, 10)],
+        [(4, 9), (4, 10), (5, 9), (5, 10)],
+        [(4, 9), (4, 10), (5, 9), (5, 10)],
+        [(4, 9), (4, 10), (5, 9), (5, 10)],
+        [(3, 9), (4, 9), (4, 10), (5, 10)],
+        [(3, 10), (3, 11), (4, 9), (4, 10)],
+        [(3, 9), (4, 9), (4, 10), (5, 10)],
+        [(3, 10), (3, 11), (4, 9), (4, 10)],
+        [(3, 10), (4, 9), (4, 10), (5, 9)],
+        [(4, 9), (4, 10), (5, 10), (5, 11)],
+        [(3, 10), (4, 9), (4, 10), (5, 9)],
+        [(4, 9), (4, 10), (5, 10), (5, 11)]
+    ];
+    let kinds = [PieceType::T, PieceType::I, PieceType::O, PieceType::S, PieceType::Z];
+    assert_eq!(fumen.pages.len(), expected.len());
+    for (i, (page, cells)) in fumen.pages.iter().zip(&expected).enumerate() {
+        let piece = page.piece.unwrap();
+        assert_eq!(piece.kind, kinds[i / 4]);
+        assert_eq!(piece.r, i as u8 % 4);
+        let mut decoded = piece.cells();
+        decoded.sort();
+        assert_eq!(&decoded, cells, "page {}", i);
+    }
+}
@@ -0,0 +1,21 @@
+use minotetris::*;
+
+#[test]
+fn same_placement_ignores_rotation_system() {
+    let board = Board::<u16>::new();
+    for &kind in &[PieceType::I, PieceType::S, PieceType::T] {
+        let mut ars = Piece::spawn(&board, kind, RotationSystem::Ars, SpinRule::TSpin);
+        ars.turn_right(&board);
+        ars.sonic_drop(&board);
+        let srs = Piece::from_cells(kind, ars.cells(), ars.r).unwrap();
+        assert_ne!(ars, srs);
+        assert!(ars.same_placement(&srs));
+
+        let mut moved = srs;
+        moved.x += 1;
+        assert!(!ars.same_placement(&moved));
+        let mut spun = srs;
+        spun.tspin = TspinType::Full;
+        assert!(!ars.same_placement(&spun));
+    }
+}
@@ -0,0 +1,86 @@
+use std::convert::TryFrom;
+
+use minotetris::*;
+use enumset::EnumSet;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+fn deal(kind: &RandomizerKind, seed: u64, count: usize) -> Vec<PieceType> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut randomizer = kind.build();
+    (0..count).map(|_| randomizer.next(&mut rng)).collect()
+}
+
+fn counts(pieces: &[PieceType]) -> [usize; 7] {
+    let mut counts = [0; 7];
+    for &piece in pieces {
+        counts[piece as usize] += 1;
+    }
+    counts
+}
+
+#[test]
+fn bags_hold_every_piece() {
+    for bag in deal(&RandomizerKind::Bag7, 1, 700).chunks(7) {
+        assert_eq!(counts(bag), [1; 7]);
+    }
+    for bag in deal(&RandomizerKind::Bag14, 1, 1400).chunks(14) {
+        assert_eq!(counts(bag), [2; 7]);
+    }
+}
+
+#[test]
+fn bag_state_follows_the_bag() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut randomizer = RandomizerKind::Bag7.build();
+    let mut bag = EnumSet::new();
+    for _ in 0..50 {
+        if bag.is_empty() {
+            bag = EnumSet::all();
+        }
+        bag.remove(randomizer.next(&mut rng));
+        assert_eq!(randomizer.bag_state(), Some(bag));
+    }
+    assert_eq!(RandomizerKind::Bag14.build().bag_state(), None);
+}
+
+#[test]
+fn uniform_is_even() {
+    for &count in &counts(&deal(&RandomizerKind::Uniform, 3, 70000)) {
+        assert!((9500..10500).contains(&count), "{}", count);
+    }
+}
+
+#[test]
+fn history_avoids_repeats() {
+    let pieces = deal(&RandomizerKind::Tgm, 4, 10000);
+    for &count in &counts(&pieces) {
+        assert!((1200..1700).contains(&count), "{}", count);
+    }
+    let repeats = pieces.windows(2).filter(|pair| pair[0] == pair[1]).count();
+    // A uniform randomizer would repeat about 1400 times.
+    assert!(repeats < 300, "{}", repeats);
+}
+
+#[test]
+fn history_never_starts_with_an_overhang() {
+    for seed in 0..200 {
+        let first = deal(&RandomizerKind::Tgm, seed, 1)[0];
+        assert!(!matches!(first, PieceType::S | PieceType::Z | PieceType::O), "{:?}", first);
+    }
+}
+
+#[test]
+fn sequence_repeats() {
+    let sequence = PieceSequence::try_from(vec![PieceType::T, PieceType::I, PieceType::T]).unwrap();
+    let pieces = deal(&RandomizerKind::Sequence(sequence.clone()), 5, 9);
+    for chunk in pieces.chunks(3) {
+        assert_eq!(chunk, sequence.pieces());
+    }
+}
+
+#[test]
+fn empty_randomizers_are_rejected() {
+    assert_eq!(PieceSequence::try_from(Vec::new()).err(), Some(RandomizerError::EmptySequence));
+    assert_eq!(BagRandomizer::new(0).err(), Some(RandomizerError::NoCopies));
+}
@@ -0,0 +1,58 @@
+use minotetris::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+#[test]
+fn compact_boards() {
+    let board: Board = "
+        hold: T
+        #.........
+        ##.#######
+    ".parse().unwrap();
+    let json = serde_json::to_string(&board).unwrap();
+    assert_eq!(json, r#"{"rows":[1019,1],"hold":"T","combo":0,"b2b":false}"#);
+    let decoded: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.rows(), board.rows());
+    assert_eq!(decoded.column_heights(), board.column_heights());
+    assert_eq!(decoded.hold, board.hold);
+}
+
+#[test]
+fn colored_boards() {
+    let position: Position = "
+        ....ttt...
+        ##L..t.###
+        ###OO.####
+    ".parse().unwrap();
+    let json = serde_json::to_string(&(&position.board, position.piece)).unwrap();
+    let (board, piece): (Board<ColoredRow>, Option<Piece>) = serde_json::from_str(&json).unwrap();
+    assert_eq!(Position { board, piece }.to_string(), position.to_string());
+}
+
+#[test]
+fn queues_continue_after_loading() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut queue = PieceQueue::new(5, RandomizerKind::Bag7.build(), &mut rng);
+    for _ in 0..3 {
+        queue.next(&mut rng);
+    }
+    let json = serde_json::to_string(&queue).unwrap();
+    let mut loaded: PieceQueue = serde_json::from_str(&json).unwrap();
+    let mut loaded_rng = rng.clone();
+    for _ in 0..20 {
+        assert_eq!(queue.next(&mut rng), loaded.next(&mut loaded_rng));
+    }
+}
+
+#[test]
+fn invalid_randomizers_are_rejected() {
+    assert!(serde_json::from_str::<RandomizerKind>(r#"{"Sequence":[]}"#).is_err());
+    let kind: RandomizerKind = serde_json::from_str(r#"{"Sequence":["T","I"]}"#).unwrap();
+    assert_eq!(serde_json::to_string(&kind).unwrap(), r#"{"Sequence":["T","I"]}"#);
+
+    let saved = |json: &str| serde_json::from_str::<SavedRandomizer>(json).map(|_| ());
+    assert!(saved(r#"{"Sequence":{"pieces":["T","I"],"index":1}}"#).is_ok());
+    assert!(saved(r#"{"Sequence":{"pieces":["T","I"],"index":2}}"#).is_err());
+    assert!(saved(r#"{"Sequence":{"pieces":[],"index":0}}"#).is_err());
+    assert!(saved(r#"{"Bag":{"copies":0,"bag":[]}}"#).is_err());
+}
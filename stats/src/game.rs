@@ -0,0 +1,161 @@
+use std::time::{Instant, Duration};
+
+use minotetris::*;
+use minobot::evaluator::StandardEvaluator;
+use minobot::bot::{Bot, BotSettings, ThinkBudget};
+use battle::BotReplay;
+use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::*;
+
+/// Everything that decides how a game is played, apart from its seed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameOptions {
+    pub evaluator: StandardEvaluator,
+    pub settings: BotSettings,
+    pub think_time: u64,
+    /// Replaces `think_time` with a fixed budget for each move, for reproducible runs.
+    #[serde(default)]
+    pub budget: Option<ThinkBudget>,
+    pub queue: u32,
+    #[serde(default)]
+    pub randomizer: RandomizerKind,
+    pub pieces: u32,
+    /// Sends garbage to the bot, to measure how it survives and downstacks.
+    #[serde(default)]
+    pub garbage: Option<GarbageOptions>
+}
+
+impl GameOptions {
+    pub fn settings_hash(&self) -> String {
+        let settings = (
+            &self.evaluator,
+            &self.settings,
+            self.think_time,
+            &self.budget,
+            self.queue,
+            &self.randomizer,
+            self.pieces,
+            &self.garbage
+        );
+        format!("{:016x}", stable_hash(serde_yaml::to_string(&settings).unwrap().as_bytes()))
+    }
+}
+
+/// Plays a game of up to `options.pieces` pieces and records it.
+pub fn play(options: &GameOptions, seed: u64) -> (Report, BotReplay) {
+    let mut board = Board::new();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut garbage_rng = StdRng::seed_from_u64(!seed);
+    let mut garbage = options.garbage.as_ref().map(|g| GarbageGenerator::new(g, &mut garbage_rng));
+    let mut garbage_rows = 0;
+    let mut initial_garbage = Vec::new();
+    if let Some(garbage) = &mut garbage {
+        initial_garbage = garbage.fill(&mut board, &mut garbage_rng);
+        garbage_rows = options.garbage.as_ref().unwrap().initial;
+    }
+    let mut replay = BotReplay::new(
+        seed,
+        options.queue.saturating_sub(1),
+        options.randomizer.clone(),
+        options.settings.rotation_system,
+        options.settings.attack.clone(),
+        initial_garbage
+    );
+    let mut bot = Bot::new(board, options.evaluator.clone(), options.settings.clone());
+
+    let mut queue = PieceQueue::new(options.queue as usize, options.randomizer.build(), &mut rng);
+    for &piece in queue.get_queue() {
+        bot.update_queue(piece);
+    }
+    if let Some(bag) = queue.bag_state() {
+        bot.set_bag(bag);
+    }
+
+    let think_time = Duration::from_millis(options.think_time);
+    let mut total_think_time = Duration::from_secs(0);
+    let mut report = Report {
+        seed,
+        settings_hash: options.settings_hash(),
+        ..Report::default()
+    };
+    for _ in 0..options.pieces {
+        let start = Instant::now();
+        if let Some(budget) = &options.budget {
+            report.thinks += bot.think_for(budget).0;
+        } else {
+            while start.elapsed() < think_time {
+                bot.think();
+                report.thinks += 1;
+            }
+        }
+        total_think_time += start.elapsed();
+
+        let prev_board = bot.root().board.clone();
+        let node = match bot.next_move() {
+            Some(node) if !node.lock.block_out => node,
+            _ => {
+                report.topped_out = true;
+                break;
+            }
+        };
+        let is_t = node.mv.kind == PieceType::T;
+        let line_clears = match node.mv.tspin {
+            TspinType::None => &mut report.line_clears[..],
+            TspinType::Mini if is_t => &mut report.mini_tspin_clears[..],
+            TspinType::Full if is_t => &mut report.tspin_clears[..],
+            TspinType::Mini => &mut report.mini_spin_clears[..],
+            TspinType::Full => &mut report.spin_clears[..]
+        };
+        line_clears[node.lock.lines_cleared as usize] += 1;
+        report.pieces += 1;
+        if node.uses_hold {
+            report.holds += 1;
+        }
+        if node.lock.perfect_clear {
+            report.perfect_clears += 1;
+        }
+        report.attack += node.attack;
+        replay.record(
+            node.mv,
+            node.uses_hold,
+            node.lock,
+            node.attack,
+            garbage_holes(&node.board, node.garbage_received)
+        );
+        let height = node.board.column_heights().iter().copied().max().unwrap();
+        report.max_height = report.max_height.max(height);
+        let cleared = garbage_cleared(&prev_board, &node.mv, garbage_rows);
+        garbage_rows = garbage_rows - cleared + node.garbage_received;
+        report.garbage_cleared += cleared;
+        report.garbage_received += node.garbage_received;
+        report.garbage_cancelled += node.garbage_cancelled;
+
+        let mut pieces_used = 1;
+        if prev_board.hold.is_none() && node.uses_hold {
+            pieces_used += 1;
+        }
+        for _ in 0..pieces_used {
+            queue.next(&mut rng);
+            let new = *queue.get_queue().back().unwrap();
+            bot.update_queue(new);
+        }
+        if let Some(garbage) = &mut garbage {
+            for batch in garbage.send(report.pieces, &mut garbage_rng) {
+                bot.add_garbage(batch);
+            }
+        }
+    }
+    if report.pieces > 0 {
+        report.garbage_cleared_per_piece = report.garbage_cleared as f64 / report.pieces as f64;
+    }
+    report.think_time_ms = total_think_time.as_secs_f64() * 1000.0;
+    if report.thinks > 0 {
+        report.ms_per_think = total_think_time.as_millis() as f64 / report.thinks as f64;
+    }
+    report.transposition_hit_rate = bot.data.transpositions.hit_rate();
+    replay.finish(&bot.root().board);
+    (report, replay)
+}
@@ -0,0 +1,107 @@
+use minotetris::*;
+use minobot::bot::GarbageBatch;
+use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GarbageOptions {
+    /// Rows of garbage on the board before the first piece, as in a cheese race.
+    pub initial: u32,
+    /// Garbage is sent every this many pieces, or never if 0.
+    pub interval: u32,
+    /// Lines sent each time.
+    pub lines: u32,
+    /// Number of pieces the bot can place between garbage being sent and it arriving.
+    pub delay: u32,
+    /// Chance of the hole moving to another column from one line to the next.
+    /// 0 gives clean garbage and 1 gives cheese.
+    pub messiness: f64
+}
+
+impl Default for GarbageOptions {
+    fn default() -> Self {
+        GarbageOptions {
+            initial: 0,
+            interval: 4,
+            lines: 2,
+            delay: 0,
+            messiness: 0.3
+        }
+    }
+}
+
+impl GarbageOptions {
+    /// Panics on options the generator can't use.
+    pub fn validate(&self) {
+        assert!((0.0..=1.0).contains(&self.messiness), "garbage messiness needs to be between 0 and 1");
+    }
+}
+
+pub struct GarbageGenerator<'a> {
+    options: &'a GarbageOptions,
+    hole: i32
+}
+
+impl<'a> GarbageGenerator<'a> {
+    pub fn new(options: &'a GarbageOptions, rng: &mut impl Rng) -> Self {
+        Self {
+            options,
+            hole: rng.gen_range(0, 10)
+        }
+    }
+
+    fn next_hole(&mut self, rng: &mut impl Rng) -> i32 {
+        if rng.gen_bool(self.options.messiness) {
+            // Any column but the current one.
+            self.hole = (self.hole + rng.gen_range(1, 10)) % 10;
+        }
+        self.hole
+    }
+
+    /// Fills the bottom of `board` with the initial garbage, returning its holes.
+    pub fn fill(&mut self, board: &mut Board, rng: &mut impl Rng) -> Vec<i32> {
+        let holes: Vec<_> = (0..self.options.initial).map(|_| self.next_hole(rng)).collect();
+        board.add_garbage(&holes);
+        holes
+    }
+
+    /// Garbage sent after `pieces` pieces have been placed, as one batch per line
+    /// so each can have its own hole.
+    pub fn send(&mut self, pieces: u32, rng: &mut impl Rng) -> Vec<GarbageBatch> {
+        if self.options.interval == 0 || pieces % self.options.interval != 0 {
+            return Vec::new();
+        }
+        (0..self.options.lines)
+            .map(|_| GarbageBatch {
+                lines: 1,
+                hole: Some(self.next_hole(rng)),
+                delay: self.options.delay
+            })
+            .collect()
+    }
+}
+
+/// Number of the bottom `garbage_rows` rows of `board` that `piece` clears.
+pub fn garbage_cleared(board: &Board, piece: &Piece, garbage_rows: u32) -> u32 {
+    let cells = piece.cells();
+    (0..garbage_rows as i32)
+        .filter(|&y| {
+            let mut row = board.rows()[y as usize];
+            for &(x, cell_y) in &cells {
+                if cell_y == y {
+                    row.set(x as usize, CellType::Garbage);
+                }
+            }
+            row.filled()
+        })
+        .count() as u32
+}
+
+/// Holes of the bottom `rows` rows of `board`, in the order `Board::add_garbage` takes them.
+pub fn garbage_holes(board: &Board, rows: u32) -> Vec<i32> {
+    (0..rows as usize)
+        .rev()
+        .map(|y| (0..10).find(|&x| !board.rows()[y].get(x)).unwrap_or(0) as i32)
+        .collect()
+}
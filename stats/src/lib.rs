@@ -0,0 +1,6 @@
+mod report;
+mod garbage;
+mod game;
+pub use report::*;
+pub use garbage::*;
+pub use game::*;
@@ -1,75 +1,114 @@
-use std::io::{stdin, BufReader};
-use std::time::{Instant, Duration};
+use std::io::{stdin, BufReader, BufWriter};
+use std::fs::File;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use minotetris::*;
-use minobot::evaluator::StandardEvaluator;
-use minobot::bot::{Bot, BotSettings};
+use battle::Replay;
 use serde::{Serialize, Deserialize};
 
+use stats::*;
+
 #[derive(Serialize, Deserialize)]
 struct Options {
-    evaluator: StandardEvaluator,
-    settings: BotSettings,
-    think_time: u64,
-    queue: u32,
-    pieces: u32
+    #[serde(flatten)]
+    game: GameOptions,
+    /// Seed for the piece RNG of the first game, which is counted up for every other game.
+    /// A random one is picked and printed if this is left out.
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default = "one")]
+    games: u32,
+    /// Number of games played at once.
+    #[serde(default = "one")]
+    threads: u32,
+    #[serde(default)]
+    format: OutputFormat,
+    /// Directory to write a replay of each game to, named after its seed.
+    #[serde(default)]
+    replays: Option<String>
+}
+
+fn one() -> u32 {
+    1
 }
 
 fn main() {
     let stdin = BufReader::new(stdin());
-    let mut rng = rand::thread_rng();
-
-    let board = Board::new();
     let options: Options = serde_yaml::from_reader(stdin).unwrap();
-    let mut bot = Bot::new(board, options.evaluator, options.settings);
-    
-    let mut queue = PieceQueue::new(options.queue as usize, &mut rng);
-    for &piece in queue.get_queue() {
-        bot.update_queue(piece);
+    if let Some(budget) = &options.game.budget {
+        assert!(budget.is_limited(), "the think budget needs a limit on thinks, nodes or time");
+    }
+    assert!(options.games > 0, "at least one game needs to be played");
+    if let Some(garbage) = &options.game.garbage {
+        garbage.validate();
+    }
+    let seed = options.seed.unwrap_or_else(rand::random);
+    // On stderr so it is there even if the run is cut short, without breaking JSON or CSV output.
+    eprintln!("Seed: {}", seed);
+    if let Some(dir) = &options.replays {
+        std::fs::create_dir_all(dir).unwrap();
     }
 
-    let think_time = Duration::from_millis(options.think_time);
-    let mut thinks = 0;
-    let mut total_think_time = Duration::from_secs(0);
-    
-    let mut line_clears = [0; 5];
-    let mut full_tspin_clears = [0; 4];
-    let mut mini_tspin_clears = [0; 3];
-    for _ in 0..options.pieces {
-        let start = Instant::now();
-        while start.elapsed() < think_time {
-            bot.think();
-            thinks += 1;
+    let next = AtomicU32::new(0);
+    let reports = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| loop {
+                let game = next.fetch_add(1, Ordering::Relaxed);
+                if game >= options.games {
+                    break;
+                }
+                let (report, replay) = play(&options.game, seed.wrapping_add(game as u64));
+                if let Some(dir) = &options.replays {
+                    let file = File::create(format!("{}/{}.json", dir, report.seed)).unwrap();
+                    Replay::Bot(Box::new(replay)).write(BufWriter::new(file)).unwrap();
+                }
+                reports.lock().unwrap().push(report);
+            });
         }
-        total_think_time += start.elapsed();
-        
-        let prev_hold = bot.root.board.hold.is_some();
-        let node = bot.next_move().unwrap();
-        let line_clears = match node.mv.tspin {
-            TspinType::None => &mut line_clears[..],
-            TspinType::Mini => &mut mini_tspin_clears[..],
-            TspinType::Full => &mut full_tspin_clears[..]
-        };
-        line_clears[node.lock.lines_cleared as usize] += 1;
-        
-        let mut pieces_used = 1;
-        if !prev_hold && node.uses_hold {
-            pieces_used += 1;
+    });
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by_key(|report| report.seed.wrapping_sub(seed));
+    let summary = summarize(&reports);
+
+    let all_spins = options.game.settings.spin_rule != SpinRule::TSpin;
+    match options.format {
+        OutputFormat::Text if reports.len() == 1 => reports[0].print_text(all_spins, options.game.garbage.is_some()),
+        OutputFormat::Text => {
+            for (i, report) in reports.iter().enumerate() {
+                println!(
+                    "Game {} (seed {}): {} pieces, {} attack, max height {}{}",
+                    i + 1,
+                    report.seed,
+                    report.pieces,
+                    report.attack,
+                    report.max_height,
+                    if report.topped_out { ", topped out" } else { "" }
+                );
+            }
+            println!();
+            for metric in &summary {
+                println!(
+                    "{}: {:.3} ± {:.3} (standard deviation {:.3})",
+                    metric.metric,
+                    metric.mean,
+                    metric.mean - metric.ci_low,
+                    metric.std_dev
+                );
+            }
         }
-        for _ in 0..pieces_used {
-            queue.next(&mut rng);
-            let new = *queue.get_queue().back().unwrap();
-            bot.update_queue(new);
+        OutputFormat::Json => {
+            let results = Results {
+                games: &reports,
+                summary: &summary
+            };
+            println!("{}", serde_json::to_string_pretty(&results).unwrap());
+        }
+        OutputFormat::Csv => {
+            print_csv(&reports);
+            println!();
+            print_summary_csv(&summary);
         }
-    }
-    println!("ms/think: {}", total_think_time.as_millis() as f64 / thinks as f64);
-    for (line_clear_type, &lines_cleared) in line_clears.iter().enumerate() {
-        println!("Clear {}: {}", line_clear_type, lines_cleared);
-    }
-    for (line_clear_type, &lines_cleared) in mini_tspin_clears.iter().enumerate() {
-        println!("T spin mini {}: {}", line_clear_type, lines_cleared);
-    }
-    for (line_clear_type, &lines_cleared) in full_tspin_clears.iter().enumerate() {
-        println!("T spin {}: {}", line_clear_type, lines_cleared);
     }
 }
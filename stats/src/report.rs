@@ -0,0 +1,220 @@
+use serde::{Serialize, Deserialize};
+use serde_yaml::Value;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv
+}
+
+/// Everything measured over one game.
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct Report {
+    pub seed: u64,
+    /// Hash of every option except the seed, to tell apart runs with different settings.
+    pub settings_hash: String,
+    pub pieces: u32,
+    /// Whether the game ended early because the bot topped out.
+    pub topped_out: bool,
+    pub holds: u32,
+    pub max_height: i32,
+    pub perfect_clears: u32,
+    pub attack: u32,
+    /// Garbage lines that were inserted into the board.
+    pub garbage_received: u32,
+    /// Garbage lines cancelled by attacking before they arrived.
+    pub garbage_cancelled: u32,
+    /// Garbage lines cleared, including those on the board from the start.
+    pub garbage_cleared: u32,
+    pub garbage_cleared_per_piece: f64,
+    pub thinks: u32,
+    pub think_time_ms: f64,
+    pub ms_per_think: f64,
+    pub transposition_hit_rate: f64,
+    pub line_clears: [u32; 5],
+    pub mini_tspin_clears: [u32; 3],
+    pub tspin_clears: [u32; 4],
+    pub mini_spin_clears: [u32; 5],
+    pub spin_clears: [u32; 5]
+}
+
+impl Report {
+    pub fn print_text(&self, all_spins: bool, garbage: bool) {
+        println!("Seed: {}", self.seed);
+        println!("ms/think: {}", self.ms_per_think);
+        println!("Transposition hit rate: {}", self.transposition_hit_rate);
+        for (line_clear_type, &lines_cleared) in self.line_clears.iter().enumerate() {
+            println!("Clear {}: {}", line_clear_type, lines_cleared);
+        }
+        for (line_clear_type, &lines_cleared) in self.mini_tspin_clears.iter().enumerate() {
+            println!("T spin mini {}: {}", line_clear_type, lines_cleared);
+        }
+        for (line_clear_type, &lines_cleared) in self.tspin_clears.iter().enumerate() {
+            println!("T spin {}: {}", line_clear_type, lines_cleared);
+        }
+        if all_spins {
+            for (line_clear_type, &lines_cleared) in self.mini_spin_clears.iter().enumerate() {
+                println!("Spin mini {}: {}", line_clear_type, lines_cleared);
+            }
+            for (line_clear_type, &lines_cleared) in self.spin_clears.iter().enumerate() {
+                println!("Spin {}: {}", line_clear_type, lines_cleared);
+            }
+        }
+        println!("Pieces: {}", self.pieces);
+        println!("Holds: {}", self.holds);
+        println!("Max height: {}", self.max_height);
+        println!("Perfect clears: {}", self.perfect_clears);
+        println!("Attack: {}", self.attack);
+        if garbage {
+            println!("Garbage received: {}", self.garbage_received);
+            println!("Garbage cancelled: {}", self.garbage_cancelled);
+            println!("Garbage cleared: {}", self.garbage_cleared);
+            println!("Garbage cleared per piece: {}", self.garbage_cleared_per_piece);
+        }
+        if self.topped_out {
+            println!("Topped out");
+        }
+    }
+
+    /// Column names and values in field order, with arrays split into one column per entry.
+    pub fn columns(&self) -> Vec<(String, String)> {
+        let mut columns = Vec::new();
+        flatten(String::new(), &serde_yaml::to_value(self).unwrap(), &mut columns);
+        columns
+    }
+}
+
+fn flatten(name: String, value: &Value, columns: &mut Vec<(String, String)>) {
+    match value {
+        Value::Sequence(values) => for (i, value) in values.iter().enumerate() {
+            flatten(format!("{}_{}", name, i), value, columns);
+        },
+        Value::Mapping(fields) => for (field, value) in fields {
+            let field = field.as_str().unwrap();
+            let name = if name.is_empty() {
+                field.to_owned()
+            } else {
+                format!("{}_{}", name, field)
+            };
+            flatten(name, value, columns);
+        },
+        Value::String(value) => columns.push((name, value.clone())),
+        Value::Number(value) => columns.push((name, value.to_string())),
+        Value::Bool(value) => columns.push((name, value.to_string())),
+        Value::Null => columns.push((name, String::new()))
+    }
+}
+
+pub fn print_csv<'a>(reports: impl IntoIterator<Item=&'a Report>) {
+    let mut header = true;
+    for report in reports {
+        let columns = report.columns();
+        if header {
+            header = false;
+            println!("{}", columns.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(","));
+        }
+        println!("{}", columns.iter().map(|(_, value)| value.as_str()).collect::<Vec<_>>().join(","));
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is the same across builds.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Mean of a metric over all games, with a 95% confidence interval from Student's t-distribution.
+#[derive(Serialize, Clone, Debug)]
+pub struct Summary {
+    pub metric: String,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub ci_low: f64,
+    pub ci_high: f64
+}
+
+#[derive(Serialize)]
+pub struct Results<'a> {
+    pub games: &'a [Report],
+    pub summary: &'a [Summary]
+}
+
+/// 97.5th percentile of Student's t-distribution with 1 to 30 degrees of freedom.
+const T_975: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042
+];
+
+/// Multiple of the standard error on each side of the mean for a 95% confidence interval.
+fn t_975(degrees_of_freedom: usize) -> f64 {
+    match T_975.get(degrees_of_freedom - 1) {
+        Some(&t) => t,
+        None => {
+            // Past the table, the first terms of the expansion around the normal quantile
+            // are accurate to a few parts in ten thousand.
+            let z: f64 = 1.959964;
+            let df = degrees_of_freedom as f64;
+            z + (z.powi(3) + z) / (4.0 * df) + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df * df)
+        }
+    }
+}
+
+/// Returns an empty summary if there are no reports.
+pub fn summarize(reports: &[Report]) -> Vec<Summary> {
+    let columns: Vec<_> = reports.iter().map(Report::columns).collect();
+    let n = reports.len() as f64;
+    let mut summary = Vec::new();
+    let first = match columns.first() {
+        Some(first) => first,
+        None => return summary
+    };
+    for (i, (metric, _)) in first.iter().enumerate() {
+        if metric == "seed" || metric == "settings_hash" {
+            continue;
+        }
+        let values: Vec<f64> = columns
+            .iter()
+            .map(|c| match c[i].1.as_str() {
+                "true" => 1.0,
+                "false" => 0.0,
+                value => value.parse().unwrap()
+            })
+            .collect();
+        let mean = values.iter().sum::<f64>() / n;
+        let (std_dev, half_width) = if reports.len() > 1 {
+            let std_dev = (values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0)).sqrt();
+            (std_dev, t_975(reports.len() - 1) * std_dev / n.sqrt())
+        } else {
+            (0.0, 0.0)
+        };
+        summary.push(Summary {
+            metric: metric.clone(),
+            mean,
+            std_dev,
+            ci_low: mean - half_width,
+            ci_high: mean + half_width
+        });
+    }
+    summary
+}
+
+pub fn print_summary_csv(summary: &[Summary]) {
+    println!("metric,mean,std_dev,ci_low,ci_high");
+    for metric in summary {
+        println!(
+            "{},{},{},{},{}",
+            metric.metric,
+            metric.mean,
+            metric.std_dev,
+            metric.ci_low,
+            metric.ci_high
+        );
+    }
+}
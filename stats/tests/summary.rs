@@ -0,0 +1,30 @@
+use stats::*;
+
+fn report(seed: u64, attack: u32) -> Report {
+    Report {
+        seed,
+        attack,
+        ..Report::default()
+    }
+}
+
+#[test]
+fn no_games_no_summary() {
+    assert!(summarize(&[]).is_empty());
+}
+
+#[test]
+fn small_samples_use_t_distribution() {
+    let summary = summarize(&[report(0, 10), report(1, 20)]);
+    let attack = summary.iter().find(|s| s.metric == "attack").unwrap();
+    assert_eq!(attack.mean, 15.0);
+    // Standard error 5 with one degree of freedom.
+    assert!((attack.ci_high - attack.mean - 12.706 * 5.0).abs() < 1e-9);
+
+    let reports: Vec<_> = (0..200).map(|i| report(i, i as u32 % 7)).collect();
+    let summary = summarize(&reports);
+    let attack = summary.iter().find(|s| s.metric == "attack").unwrap();
+    let half_width = attack.ci_high - attack.mean;
+    let normal = 1.96 * attack.std_dev / (reports.len() as f64).sqrt();
+    assert!(half_width > normal && half_width < normal * 1.01);
+}
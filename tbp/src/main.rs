@@ -0,0 +1,147 @@
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::fs::File;
+use std::collections::VecDeque;
+
+use minotetris::*;
+use minobot::evaluator::StandardEvaluator;
+use minobot::bot::{BotSettings, draw_from_bag};
+use minobot::{BotHandle, BotMove};
+use serde::{Serialize, Deserialize};
+use enumset::EnumSet;
+
+mod protocol;
+use protocol::*;
+
+#[derive(Serialize, Deserialize, Default)]
+struct Options {
+    evaluator: StandardEvaluator,
+    settings: BotSettings
+}
+
+struct Game {
+    bot: BotHandle,
+    board: Board,
+    queue: VecDeque<PieceType>,
+    /// Pieces left in the current 7-bag after the last piece in `queue`, if known.
+    bag: Option<EnumSet<PieceType>>,
+    suggestion: Option<BotMove>
+}
+
+impl Game {
+    fn new(board: Board, queue: VecDeque<PieceType>, bag: Option<EnumSet<PieceType>>, options: &Options) -> Self {
+        let bot = BotHandle::new(board.clone(), options.evaluator.clone(), options.settings.clone());
+        for &piece in &queue {
+            bot.add_piece(piece);
+        }
+        if let Some(bag) = bag {
+            bot.set_bag(bag);
+        }
+        bot.begin_thinking();
+        Self {
+            bot,
+            board,
+            queue,
+            bag,
+            suggestion: None
+        }
+    }
+
+    fn new_piece(&mut self, piece: PieceType) {
+        self.queue.push_back(piece);
+        self.bag = self.bag.and_then(|bag| draw_from_bag(bag, piece));
+        self.bot.add_piece(piece);
+    }
+
+    fn play(&mut self, piece: Piece, options: &Options) {
+        let uses_hold = Some(&piece.kind) != self.queue.front();
+        let in_sync = self.suggestion
+            .take()
+            .is_some_and(|mv| mv.uses_hold == uses_hold && mv.mv.same_placement(&piece));
+        if let Some(current) = self.queue.pop_front() {
+            if uses_hold && self.board.hold.replace(current).is_none() {
+                self.queue.pop_front();
+            }
+        }
+        self.board.lock_piece(piece);
+        if in_sync {
+            self.bot.begin_thinking();
+        } else {
+            *self = Self::new(self.board.clone(), self.queue.clone(), self.bag, options);
+        }
+    }
+}
+
+fn send(message: &BotMessage) {
+    let stdout = stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer(&mut stdout, message).unwrap();
+    writeln!(stdout).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn main() {
+    let options: Options = match std::env::args().nth(1) {
+        Some(path) => serde_yaml::from_reader(BufReader::new(File::open(path).unwrap())).unwrap(),
+        None => Options::default()
+    };
+
+    send(&BotMessage::Info {
+        name: "MinoBot",
+        version: env!("CARGO_PKG_VERSION"),
+        author: "KSean222",
+        features: &[]
+    });
+
+    let mut game: Option<Game> = None;
+    let stdin = stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let message = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("Invalid message {:?}: {}", line, err);
+                continue;
+            }
+        };
+        match message {
+            FrontendMessage::Rules => send(&BotMessage::Ready),
+            FrontendMessage::Start(start) => {
+                let queue = start.queue
+                    .iter()
+                    .map(|&piece| piece.into())
+                    .collect();
+                game = Some(Game::new(start.board(), queue, start.bag(), &options));
+            }
+            FrontendMessage::Stop => game = None,
+            FrontendMessage::Suggest => if let Some(game) = &mut game {
+                if game.suggestion.is_none() {
+                    game.suggestion = game.bot.next_move();
+                }
+                let message = if let Some(mv) = &game.suggestion {
+                    let nps = mv.thinks as f64 / mv.think_time.as_secs_f64();
+                    BotMessage::Suggestion {
+                        moves: vec![mv.mv.into()],
+                        move_info: Some(MoveInfo {
+                            nodes: mv.thinks,
+                            nps: if nps.is_finite() { nps } else { 0.0 },
+                            extra: path_name(&mv.path, mv.uses_hold)
+                        })
+                    }
+                } else {
+                    BotMessage::Suggestion {
+                        moves: Vec::new(),
+                        move_info: None
+                    }
+                };
+                send(&message);
+            },
+            FrontendMessage::Play { mv } => if let Some(game) = &mut game {
+                game.play(mv.piece(), &options);
+            },
+            FrontendMessage::NewPiece { piece } => if let Some(game) = &mut game {
+                game.new_piece(piece.into());
+            },
+            FrontendMessage::Quit => break
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+
+use minotetris::*;
+use minobot::pathfinder::PathfinderMove;
+use enumset::EnumSet;
+use serde::{Serialize, Deserialize};
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start(Start),
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move
+    },
+    NewPiece {
+        piece: PieceKind
+    },
+    Quit
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Start {
+    pub hold: Option<PieceKind>,
+    pub queue: Vec<PieceKind>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    pub board: Vec<[Option<char>; 10]>,
+    #[serde(default)]
+    pub randomizer: Option<Randomizer>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Randomizer {
+    SevenBag {
+        bag_state: Vec<PieceKind>
+    },
+    #[serde(other)]
+    Unknown
+}
+
+impl Start {
+    pub fn board(&self) -> Board {
+        let mut field = [0u16; 40];
+        for (row, cells) in field.iter_mut().zip(self.board.iter()) {
+            for (x, cell) in cells.iter().enumerate() {
+                if cell.is_some() {
+                    row.set(x, CellType::Garbage);
+                }
+            }
+        }
+        let mut board = Board::new();
+        board.set_field(field);
+        board.hold = self.hold.map(PieceType::from);
+        board.combo = self.combo;
+        board.b2b = self.back_to_back;
+        board
+    }
+    /// Pieces left in the current 7-bag after the last piece in the queue, if the game uses 7-bags.
+    pub fn bag(&self) -> Option<EnumSet<PieceType>> {
+        match &self.randomizer {
+            Some(Randomizer::SevenBag { bag_state }) => {
+                Some(bag_state.iter().map(|&piece| PieceType::from(piece)).collect())
+            }
+            _ => None
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: &'static str,
+        version: &'static str,
+        author: &'static str,
+        features: &'static [&'static str]
+    },
+    Ready,
+    Suggestion {
+        moves: Vec<Move>,
+        move_info: Option<MoveInfo>
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin
+}
+
+impl Move {
+    pub fn piece(&self) -> Piece {
+        Piece {
+            kind: self.location.kind.into(),
+            x: self.location.x,
+            y: self.location.y,
+            r: self.location.orientation as u8,
+            tspin: self.spin.into(),
+            // TBP locations are always given in SRS.
+            system: RotationSystem::Srs,
+            spin_rule: SpinRule::TSpin
+        }
+    }
+}
+
+impl From<Piece> for Move {
+    fn from(piece: Piece) -> Self {
+        Self {
+            location: PieceLocation {
+                kind: piece.kind.into(),
+                orientation: match piece.r {
+                    0 => Orientation::North,
+                    1 => Orientation::East,
+                    2 => Orientation::South,
+                    3 => Orientation::West,
+                    _ => unreachable!()
+                },
+                x: piece.x,
+                y: piece.y
+            },
+            spin: piece.tspin.into()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub kind: PieceKind,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MoveInfo {
+    pub nodes: u32,
+    pub nps: f64,
+    pub extra: String
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum PieceKind {
+    J,
+    L,
+    S,
+    T,
+    Z,
+    I,
+    O
+}
+
+impl From<PieceKind> for PieceType {
+    fn from(kind: PieceKind) -> Self {
+        match kind {
+            PieceKind::J => Self::J,
+            PieceKind::L => Self::L,
+            PieceKind::S => Self::S,
+            PieceKind::T => Self::T,
+            PieceKind::Z => Self::Z,
+            PieceKind::I => Self::I,
+            PieceKind::O => Self::O
+        }
+    }
+}
+
+impl From<PieceType> for PieceKind {
+    fn from(kind: PieceType) -> Self {
+        match kind {
+            PieceType::J => Self::J,
+            PieceType::L => Self::L,
+            PieceType::S => Self::S,
+            PieceType::T => Self::T,
+            PieceType::Z => Self::Z,
+            PieceType::I => Self::I,
+            PieceType::O => Self::O
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    None,
+    Mini,
+    Full
+}
+
+impl From<Spin> for TspinType {
+    fn from(spin: Spin) -> Self {
+        match spin {
+            Spin::None => Self::None,
+            Spin::Mini => Self::Mini,
+            Spin::Full => Self::Full
+        }
+    }
+}
+
+impl From<TspinType> for Spin {
+    fn from(tspin: TspinType) -> Self {
+        match tspin {
+            TspinType::None => Self::None,
+            TspinType::Mini => Self::Mini,
+            TspinType::Full => Self::Full
+        }
+    }
+}
+
+pub fn path_name(path: &VecDeque<PathfinderMove>, uses_hold: bool) -> String {
+    let mut name = String::new();
+    if uses_hold {
+        name.push_str("hold ");
+    }
+    for mv in path {
+        name.push_str(match mv {
+            PathfinderMove::Left => "left ",
+            PathfinderMove::Right => "right ",
+            PathfinderMove::RotLeft => "ccw ",
+            PathfinderMove::RotRight => "cw ",
+            PathfinderMove::Rot180 => "180 ",
+            PathfinderMove::SonicDrop => "sonic_drop "
+        });
+    }
+    name.push_str("hard_drop");
+    name
+}
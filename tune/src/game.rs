@@ -0,0 +1,38 @@
+use minobot::evaluator::StandardEvaluator;
+use stats::{GameOptions, Report};
+use serde::{Serialize, Deserialize};
+
+use crate::Options;
+
+/// What weights are scored on.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum Fitness {
+    /// Attack per piece. Pieces missing after topping out count as sending nothing.
+    AttackPerPiece,
+    /// Fraction of the pieces placed before topping out, usually while `garbage` arrives.
+    Survival
+}
+
+impl Fitness {
+    pub fn score(&self, report: &Report, options: &Options) -> f64 {
+        match self {
+            Fitness::AttackPerPiece => report.attack as f64 / options.pieces as f64,
+            Fitness::Survival => report.pieces as f64 / options.pieces as f64
+        }
+    }
+}
+
+/// Plays a game of up to `options.pieces` pieces the same way `stats` does.
+pub fn play(options: &Options, evaluator: StandardEvaluator, seed: u64) -> Report {
+    let options = GameOptions {
+        evaluator,
+        settings: options.settings.clone(),
+        think_time: 0,
+        budget: Some(options.budget.clone()),
+        queue: options.queue,
+        randomizer: options.randomizer.clone(),
+        pieces: options.pieces,
+        garbage: options.garbage.clone()
+    };
+    stats::play(&options, seed).0
+}
@@ -0,0 +1,157 @@
+use minobot::evaluator::StandardEvaluator;
+use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+
+/// Every weight of the evaluator, in a fixed order.
+pub fn weights(evaluator: &mut StandardEvaluator) -> Vec<&mut i32> {
+    // Destructured so that new weights can't be forgotten here.
+    let StandardEvaluator {
+        holes,
+        holes_sq,
+        hole_depths,
+        hole_depths_sq,
+        move_height,
+        move_height_sq,
+        move_dist,
+        max_height,
+        max_height_sq,
+        bumpiness,
+        bumpiness_sq,
+        row_transitions,
+        row_transitions_sq,
+        well_depth,
+        max_well_depth,
+        line_clear,
+        mini_clear,
+        tspin_clear,
+        mini_spin_clear,
+        spin_clear,
+        perfect_clear,
+        combo_garbage,
+        wasted_t,
+        tslot,
+        garbage_sent,
+        garbage_cancelled,
+        pending_garbage
+    } = evaluator;
+    let mut weights = vec![
+        holes,
+        holes_sq,
+        hole_depths,
+        hole_depths_sq,
+        move_height,
+        move_height_sq,
+        move_dist,
+        max_height,
+        max_height_sq,
+        bumpiness,
+        bumpiness_sq,
+        row_transitions,
+        row_transitions_sq,
+        well_depth,
+        max_well_depth,
+        perfect_clear,
+        combo_garbage,
+        wasted_t,
+        tslot,
+        garbage_sent,
+        garbage_cancelled,
+        pending_garbage
+    ];
+    weights.extend(line_clear.iter_mut());
+    weights.extend(mini_clear.iter_mut());
+    weights.extend(tspin_clear.iter_mut());
+    weights.extend(mini_spin_clear.iter_mut());
+    weights.extend(spin_clear.iter_mut());
+    weights
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GeneticOptions {
+    pub population: usize,
+    pub generations: u32,
+    /// Number of the best individuals carried over to the next generation unchanged.
+    pub elite: usize,
+    /// Parents are the best of this many random individuals.
+    pub tournament: usize,
+    /// Chance of each weight being mutated.
+    pub mutation_rate: f64,
+    /// Largest mutation, as a fraction of the weight.
+    pub mutation_scale: f64,
+    /// Largest mutation of weights close to 0.
+    pub min_mutation: i32
+}
+
+impl Default for GeneticOptions {
+    fn default() -> Self {
+        GeneticOptions {
+            population: 16,
+            generations: 50,
+            elite: 2,
+            tournament: 3,
+            mutation_rate: 0.2,
+            mutation_scale: 0.25,
+            min_mutation: 10
+        }
+    }
+}
+
+impl GeneticOptions {
+    /// Panics on options that can't breed a generation.
+    pub fn validate(&self) {
+        assert!(self.population > 0, "the population can't be empty");
+        assert!(self.elite <= self.population, "the elite can't be larger than the population");
+        assert!(
+            (0.0..=1.0).contains(&self.mutation_rate),
+            "the mutation rate needs to be between 0 and 1"
+        );
+        assert!(self.mutation_scale >= 0.0, "the mutation scale can't be negative");
+        assert!(self.min_mutation >= 0, "the minimum mutation can't be negative");
+    }
+
+    /// A population made of `base` and mutations of it.
+    pub fn initial(&self, base: &StandardEvaluator, rng: &mut impl Rng) -> Vec<StandardEvaluator> {
+        let mut population = vec![base.clone()];
+        while population.len() < self.population {
+            let mut individual = base.clone();
+            self.mutate(&mut individual, rng);
+            population.push(individual);
+        }
+        population
+    }
+
+    /// Breeds the next generation from a population sorted best first.
+    pub fn next_generation(&self, ranked: &[StandardEvaluator], rng: &mut impl Rng) -> Vec<StandardEvaluator> {
+        let mut population: Vec<_> = ranked.iter().take(self.elite).cloned().collect();
+        while population.len() < self.population {
+            let mut child = self.select(ranked, rng).clone();
+            let mut other = self.select(ranked, rng).clone();
+            for (weight, other) in weights(&mut child).into_iter().zip(weights(&mut other)) {
+                if rng.gen() {
+                    *weight = *other;
+                }
+            }
+            self.mutate(&mut child, rng);
+            population.push(child);
+        }
+        population
+    }
+
+    fn select<'a>(&self, ranked: &'a [StandardEvaluator], rng: &mut impl Rng) -> &'a StandardEvaluator {
+        let best = (0..self.tournament.max(1))
+            .map(|_| rng.gen_range(0, ranked.len()))
+            .min()
+            .unwrap();
+        &ranked[best]
+    }
+
+    fn mutate(&self, evaluator: &mut StandardEvaluator, rng: &mut impl Rng) {
+        for weight in weights(evaluator) {
+            if rng.gen_bool(self.mutation_rate) {
+                let range = ((weight.abs() as f64 * self.mutation_scale) as i32).max(self.min_mutation);
+                *weight += rng.gen_range(-range, range + 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{stdin, BufReader};
+use std::fs::File;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use minotetris::*;
+use minobot::evaluator::StandardEvaluator;
+use minobot::bot::{BotSettings, ThinkBudget};
+use stats::GarbageOptions;
+use serde::{Serialize, Deserialize};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+mod game;
+mod genetic;
+use game::*;
+use genetic::*;
+
+#[derive(Serialize, Deserialize)]
+pub struct Options {
+    /// Weights the first generation is made from.
+    evaluator: StandardEvaluator,
+    settings: BotSettings,
+    /// Thinking done for each move. Must be limited by thinks or nodes so runs are reproducible.
+    budget: ThinkBudget,
+    queue: u32,
+    #[serde(default)]
+    randomizer: RandomizerKind,
+    /// Length of each game in pieces.
+    pieces: u32,
+    /// Games played by each individual every generation. All individuals play the same seeds.
+    games: u32,
+    fitness: Fitness,
+    /// Garbage sent to the bot during each game, as in `stats`.
+    #[serde(default)]
+    garbage: Option<GarbageOptions>,
+    #[serde(default)]
+    genetic: GeneticOptions,
+    /// Number of games played at once.
+    threads: usize,
+    seed: u64,
+    /// The population is saved here after every generation, and resumed from if it exists.
+    checkpoint: Option<String>,
+    /// The best weights of the latest generation are written here.
+    output: String
+}
+
+impl Options {
+    /// Panics on options that would make a run fail partway through or score NaN.
+    fn validate(&self) {
+        assert!(self.budget.is_counted(), "the think budget needs a limit on thinks or nodes");
+        assert!(self.pieces > 0, "games need at least one piece");
+        assert!(self.games > 0, "individuals need to play at least one game");
+        self.genetic.validate();
+        if let Some(garbage) = &self.garbage {
+            garbage.validate();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    generation: u32,
+    population: Vec<StandardEvaluator>
+}
+
+/// Average fitness of each individual over the games played with `seeds`.
+fn evaluate(options: &Options, population: &[StandardEvaluator], seeds: &[u64]) -> Vec<f64> {
+    let next = AtomicUsize::new(0);
+    let totals = Mutex::new(vec![0.0; population.len()]);
+    let games = population.len() * seeds.len();
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| loop {
+                let game = next.fetch_add(1, Ordering::Relaxed);
+                if game >= games {
+                    break;
+                }
+                let individual = game / seeds.len();
+                let seed = seeds[game % seeds.len()];
+                let result = play(options, population[individual].clone(), seed);
+                totals.lock().unwrap()[individual] += options.fitness.score(&result, options);
+            });
+        }
+    });
+    totals
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|total| total / seeds.len() as f64)
+        .collect()
+}
+
+fn main() {
+    let stdin = BufReader::new(stdin());
+    let options: Options = serde_yaml::from_reader(stdin).unwrap();
+    options.validate();
+
+    let mut checkpoint = match options.checkpoint.as_ref().and_then(|path| File::open(path).ok()) {
+        Some(file) => {
+            let checkpoint: Checkpoint = serde_yaml::from_reader(BufReader::new(file)).unwrap();
+            println!("Resuming from generation {}", checkpoint.generation);
+            checkpoint
+        }
+        None => Checkpoint {
+            generation: 0,
+            population: options.genetic.initial(
+                &options.evaluator,
+                &mut StdRng::seed_from_u64(options.seed)
+            )
+        }
+    };
+
+    while checkpoint.generation < options.genetic.generations {
+        // Seeded by generation so that resuming gives the same run.
+        let mut rng = StdRng::seed_from_u64(options.seed.wrapping_add(checkpoint.generation as u64 + 1));
+        let seeds: Vec<u64> = (0..options.games).map(|_| rng.gen()).collect();
+        let fitness = evaluate(&options, &checkpoint.population, &seeds);
+
+        let mut ranking: Vec<usize> = (0..fitness.len()).collect();
+        ranking.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+        let ranked: Vec<_> = ranking.iter().map(|&i| checkpoint.population[i].clone()).collect();
+        println!(
+            "Generation {}: best {:.4}, mean {:.4}",
+            checkpoint.generation + 1,
+            fitness[ranking[0]],
+            fitness.iter().sum::<f64>() / fitness.len() as f64
+        );
+        serde_yaml::to_writer(File::create(&options.output).unwrap(), &ranked[0]).unwrap();
+
+        checkpoint.population = options.genetic.next_generation(&ranked, &mut rng);
+        checkpoint.generation += 1;
+        if let Some(path) = &options.checkpoint {
+            serde_yaml::to_writer(File::create(path).unwrap(), &checkpoint).unwrap();
+        }
+    }
+    println!("Best weights written to {}", options.output);
+}
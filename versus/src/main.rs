@@ -0,0 +1,157 @@
+use std::io::{stdin, BufReader, BufWriter};
+use std::fs::File;
+
+use battle::*;
+use serde::{Serialize, Deserialize};
+
+mod player;
+use player::*;
+
+/// Match options, read from stdin. Each player's options are read from their own file.
+#[derive(Serialize, Deserialize)]
+struct Options {
+    #[serde(default)]
+    game: TetrisGameConfig,
+    matches: u32,
+    seed: u64,
+    max_frames: u32,
+    /// Directory to write a replay of each match to, named after its seed.
+    #[serde(default)]
+    replays: Option<String>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Outcome {
+    P1,
+    P2,
+    Draw
+}
+
+#[derive(Default)]
+struct Totals {
+    wins: u32,
+    pieces: u32,
+    attack: u32
+}
+
+impl Totals {
+    fn add(&mut self, player: &Player) {
+        self.pieces += player.pieces;
+        self.attack += player.attack;
+    }
+
+    fn print(&self, name: &str, seconds: f64) {
+        println!(
+            "{}: {} wins, {:.2} pieces per second, {:.3} attack per piece",
+            name,
+            self.wins,
+            ratio(self.pieces as f64, seconds),
+            ratio(self.attack as f64, self.pieces as f64)
+        );
+    }
+}
+
+/// `a / b`, or 0 if `b` is 0.
+fn ratio(a: f64, b: f64) -> f64 {
+    if b > 0.0 {
+        a / b
+    } else {
+        0.0
+    }
+}
+
+fn read_player(path: Option<String>) -> PlayerOptions {
+    let path = path.expect("usage: versus <p1 options> <p2 options> < match options");
+    serde_yaml::from_reader(BufReader::new(File::open(path).unwrap())).unwrap()
+}
+
+const FPS: f64 = 60.0;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let p1_options = read_player(args.next());
+    let p2_options = read_player(args.next());
+    let stdin = BufReader::new(stdin());
+    let options: Options = serde_yaml::from_reader(stdin).unwrap();
+    println!("Seed: {}", options.seed);
+    if let Some(dir) = &options.replays {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+
+    let mut p1_totals = Totals::default();
+    let mut p2_totals = Totals::default();
+    let mut draws = 0;
+    let mut total_frames = 0;
+    for i in 0..options.matches {
+        let seed = options.seed + i as u64;
+        let mut rng = BattleRng::from_seed(seed);
+        let mut battle = TetrisBattle::new(
+            options.game.clone(),
+            &mut rng.p1,
+            options.game.clone(),
+            &mut rng.p2
+        );
+        let mut p1 = Player::new(&p1_options, battle.p1());
+        let mut p2 = Player::new(&p2_options, battle.p2());
+        let mut replay = BattleReplay::new(seed, options.game.clone(), options.game.clone());
+
+        let mut frames = 0;
+        let outcome = loop {
+            let p1_inputs = p1.inputs(battle.p1());
+            let p2_inputs = p2.inputs(battle.p2());
+            let (p1_events, p2_events) = battle.update(
+                p1_inputs,
+                &mut rng.p1,
+                p2_inputs,
+                &mut rng.p2,
+                &mut rng.garbage
+            );
+            frames += 1;
+            replay.record(p1_inputs, &p1_events, p2_inputs, &p2_events);
+            p1.update(battle.p1(), &p1_events, &p2_events);
+            p2.update(battle.p2(), &p2_events, &p1_events);
+
+            let game_over = |events: &[TetrisGameEvent]| events
+                .iter()
+                .any(|e| matches!(e, TetrisGameEvent::GameOver));
+            match (game_over(&p1_events), game_over(&p2_events)) {
+                (true, false) => break Outcome::P2,
+                (false, true) => break Outcome::P1,
+                (true, true) => break Outcome::Draw,
+                _ if frames >= options.max_frames => break Outcome::Draw,
+                _ => {}
+            }
+        };
+
+        if let Some(dir) = &options.replays {
+            replay.finish(&battle);
+            let file = File::create(format!("{}/{}.json", dir, seed)).unwrap();
+            Replay::Battle(Box::new(replay)).write(BufWriter::new(file)).unwrap();
+        }
+
+        match outcome {
+            Outcome::P1 => p1_totals.wins += 1,
+            Outcome::P2 => p2_totals.wins += 1,
+            Outcome::Draw => draws += 1
+        }
+        p1_totals.add(&p1);
+        p2_totals.add(&p2);
+        total_frames += frames;
+        println!(
+            "Match {} (seed {}): {:?} after {:.1}s, {} vs {} pieces",
+            i + 1,
+            seed,
+            outcome,
+            frames as f64 / FPS,
+            p1.pieces,
+            p2.pieces
+        );
+    }
+
+    let seconds = total_frames as f64 / FPS;
+    println!();
+    p1_totals.print("P1", seconds);
+    p2_totals.print("P2", seconds);
+    println!("Draws: {}", draws);
+    println!("Average game length: {:.1}s", ratio(seconds, options.matches as f64));
+}
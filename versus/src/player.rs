@@ -0,0 +1,173 @@
+use std::time::{Instant, Duration};
+use std::collections::VecDeque;
+
+use minobot::{BotHandle, BotMove};
+use minobot::bot::{BotSettings, GarbageBatch};
+use minobot::evaluator::StandardEvaluator;
+use minobot::pathfinder::PathfinderMove;
+use battle::*;
+use enumset::EnumSet;
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerOptions {
+    pub evaluator: StandardEvaluator,
+    pub settings: BotSettings,
+    pub think_time: u64
+}
+
+pub struct Player {
+    bot: BotHandle,
+    evaluator: StandardEvaluator,
+    settings: BotSettings,
+    think_time: Duration,
+    think_frames: u32,
+    thinking_since: Instant,
+    wait: u32,
+    driver: Option<InputDriver>,
+    desync: bool,
+    pub pieces: u32,
+    pub attack: u32
+}
+
+impl Player {
+    pub fn new(options: &PlayerOptions, game: &TetrisGame) -> Self {
+        let mut settings = options.settings.clone();
+        settings.attack = game.get_config().attack.clone();
+        settings.rotation_system = game.get_config().rotation_system;
+        settings.spin_rule = game.get_config().spin_rule;
+        settings.rotate_180 = game.get_config().rotate_180;
+        let think_frames = (options.think_time * 60 / 1000) as u32;
+        Self {
+            bot: Self::start_bot(game, &options.evaluator, &settings),
+            evaluator: options.evaluator.clone(),
+            settings,
+            think_time: Duration::from_millis(options.think_time),
+            think_frames,
+            thinking_since: Instant::now(),
+            wait: think_frames,
+            driver: None,
+            desync: false,
+            pieces: 0,
+            attack: 0
+        }
+    }
+
+    /// Starts a bot from the game's current state, between pieces.
+    fn start_bot(game: &TetrisGame, evaluator: &StandardEvaluator, settings: &BotSettings) -> BotHandle {
+        let bot = BotHandle::new(game.get_board().clone(), evaluator.clone(), settings.clone());
+        for &piece in game.get_queue() {
+            bot.add_piece(piece);
+        }
+        if let Some(bag) = game.get_bag_state() {
+            bot.set_bag(bag);
+        }
+        let lines = game.get_pending_garbage();
+        if lines > 0 {
+            bot.add_garbage(GarbageBatch {
+                lines,
+                hole: None,
+                delay: 0
+            });
+        }
+        bot.begin_thinking();
+        bot
+    }
+
+    /// Inputs for the next frame. The bot gets `think_time` of both game time and real time
+    /// before each piece, then its move is played back by an `InputDriver`.
+    pub fn inputs(&mut self, game: &TetrisGame) -> EnumSet<TetrisInput> {
+        if !matches!(game.get_state(), TetrisGameState::PieceFalling(_)) {
+            return EnumSet::new();
+        }
+        if self.driver.is_none() {
+            if self.wait > 0 {
+                self.wait -= 1;
+                return EnumSet::new();
+            }
+            let elapsed = self.thinking_since.elapsed();
+            if elapsed < self.think_time {
+                std::thread::sleep(self.think_time - elapsed);
+            }
+            let mv = self.bot.next_move();
+            self.bot.begin_thinking();
+            self.thinking_since = Instant::now();
+            match mv.map(|mv| drive(&mv, game)) {
+                Some(Ok(driver)) => self.driver = Some(driver),
+                failed => {
+                    if let Some(Err(err)) = failed {
+                        eprintln!("Can't play the bot's move: {}", err);
+                    }
+                    self.desync = true;
+                    self.wait = self.think_frames;
+                    return EnumSet::only(TetrisInput::HardDrop);
+                }
+            }
+        }
+        let driver = self.driver.as_mut().unwrap();
+        let inputs = driver.update(game);
+        if let Some(outcome) = driver.outcome() {
+            self.desync = matches!(outcome, DriveOutcome::Missed(_));
+            self.driver = None;
+            self.wait = self.think_frames;
+        }
+        inputs
+    }
+
+    /// Keeps the bot in sync with the game after a frame.
+    pub fn update(&mut self, game: &TetrisGame, events: &[TetrisGameEvent], opponent_events: &[TetrisGameEvent]) {
+        let mut resync = false;
+        for event in events {
+            match *event {
+                TetrisGameEvent::PieceSpawned { .. } => self.bot.add_piece(*game.get_queue().back().unwrap()),
+                TetrisGameEvent::PieceLocked(_) => {
+                    self.pieces += 1;
+                    resync |= self.desync;
+                    self.desync = false;
+                }
+                TetrisGameEvent::Attack(attack) => {
+                    // Any garbage that arrived during the line clear delay was just cancelled or
+                    // inserted, and inserting it is caught below, so nothing is left pending.
+                    self.attack += attack;
+                    self.bot.clear_garbage();
+                    self.thinking_since = Instant::now();
+                }
+                TetrisGameEvent::GarbageAdded(_) => resync = true,
+                _ => {}
+            }
+        }
+        if resync {
+            // The bot no longer matches the game, so start over from the real state.
+            self.bot = Self::start_bot(game, &self.evaluator, &self.settings);
+            self.thinking_since = Instant::now();
+        } else {
+            for event in opponent_events {
+                if let TetrisGameEvent::GarbageSent(lines) = *event {
+                    // Incoming garbage restarts the search, so give the bot its full think time again.
+                    self.bot.add_garbage(GarbageBatch {
+                        lines,
+                        hole: None,
+                        delay: 0
+                    });
+                    self.thinking_since = Instant::now();
+                }
+            }
+        }
+    }
+}
+
+fn drive(mv: &BotMove, game: &TetrisGame) -> Result<InputDriver, DriveError> {
+    InputDriver::new(mv.mv, mv.uses_hold, &path_inputs(&mv.path), game.get_board(), game.get_config())
+}
+
+/// The game inputs for a pathfinder path, with `SoftDrop` standing for a sonic drop.
+fn path_inputs(path: &VecDeque<PathfinderMove>) -> Vec<TetrisInput> {
+    path.iter().map(|mv| match mv {
+        PathfinderMove::Left => TetrisInput::Left,
+        PathfinderMove::Right => TetrisInput::Right,
+        PathfinderMove::RotLeft => TetrisInput::RotLeft,
+        PathfinderMove::RotRight => TetrisInput::RotRight,
+        PathfinderMove::Rot180 => TetrisInput::Rot180,
+        PathfinderMove::SonicDrop => TetrisInput::SoftDrop
+    }).collect()
+}